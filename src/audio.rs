@@ -0,0 +1,164 @@
+//! WAV encoding and decoding for the samples the engine joins together.
+
+use hound::{WavReader, WavWriter};
+use rubato::{Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction};
+
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::GenericError;
+
+/// Sampling rate of everything the engine outputs
+pub(crate) const SAMPLE_RATE: u32 = 24000;
+
+pub(crate) type AudioError = GenericError<&'static str>;
+
+/// Samples of a mono 16-bit WAV as returned by VOICEVOX Core.
+pub(crate) fn decode(wav: &[u8]) -> Result<Vec<i16>, AudioError> {
+    let reader = WavReader::new(Cursor::new(wav)).map_err(|_| AudioError::new("Failed to read synthesized audio"))?;
+    reader.into_samples::<i16>().collect::<Result<_, _>>().map_err(|_| AudioError::new("Failed to read synthesized audio"))
+}
+
+pub(crate) fn encode(samples: &[i16]) -> Vec<u8> {
+    let mut wav = Cursor::new(Vec::new());
+    let mut writer = WavWriter::new(&mut wav, hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    }).unwrap();
+
+    for sample in samples {
+        writer.write_sample(*sample).unwrap();
+    }
+
+    writer.finalize().unwrap();
+    wav.into_inner()
+}
+
+pub(crate) fn silence(seconds: f64) -> Vec<i16> {
+    vec![0; (seconds.max(0.0) * SAMPLE_RATE as f64).round() as usize]
+}
+
+/// Length of `samples` samples, in seconds
+pub(crate) fn seconds(samples: usize) -> f64 {
+    samples as f64 / SAMPLE_RATE as f64
+}
+
+/// Resolves the `src` of an SSML `<audio>` element against `base`.
+///
+/// Only relative paths that stay inside `base`, symbolic links included,
+/// are accepted.
+pub(crate) fn resolve(base: &Path, src: &Path) -> Result<PathBuf, AudioError> {
+    if src.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(AudioError::new("Audio path must be relative to the audio directory"));
+    }
+    let base = base.canonicalize().map_err(|_| AudioError::new("Audio directory not found"))?;
+    let path = base.join(src).canonicalize().map_err(|_| AudioError::new("Failed to open WAV file"))?;
+    if !path.starts_with(&base) {
+        return Err(AudioError::new("Audio path is outside the audio directory"));
+    }
+    Ok(path)
+}
+
+/// Reads a WAV file of any rate and channel count as mono at [`SAMPLE_RATE`].
+pub(crate) fn load(path: &Path, volume: f64) -> Result<Vec<i16>, AudioError> {
+    let mut reader = WavReader::open(path).map_err(|_| AudioError::new("Failed to open WAV file"))?;
+    let spec = reader.spec();
+    let samples: Vec<f64> = match spec.sample_format {
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f64;
+            reader.samples::<i32>().map(|s| s.map(|s| s as f64 / scale)).collect::<Result<_, _>>()
+        },
+        hound::SampleFormat::Float => {
+            reader.samples::<f32>().map(|s| s.map(|s| s as f64)).collect::<Result<_, _>>()
+        },
+    }.map_err(|_| AudioError::new("Failed to read WAV file"))?;
+
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples.chunks(channels).map(|frame| frame.iter().sum::<f64>() / channels as f64).collect::<Vec<_>>();
+    let mono = if spec.sample_rate == SAMPLE_RATE || mono.is_empty() {
+        mono
+    } else {
+        resample(mono, spec.sample_rate)?
+    };
+
+    Ok(mono.into_iter().map(|s| (s * volume * i16::MAX as f64).clamp(i16::MIN as f64, i16::MAX as f64) as i16).collect())
+}
+
+fn resample(samples: Vec<f64>, sample_rate: u32) -> Result<Vec<f64>, AudioError> {
+    let ratio = SAMPLE_RATE as f64 / sample_rate as f64;
+    let params = SincInterpolationParameters {
+        sinc_len: 256,
+        f_cutoff: 0.95,
+        interpolation: SincInterpolationType::Linear,
+        oversampling_factor: 256,
+        window: WindowFunction::BlackmanHarris2,
+    };
+    let length = (samples.len() as f64 * ratio).round() as usize;
+    let mut resampler = SincFixedIn::<f64>::new(ratio, 1.0, params, samples.len(), 1)
+        .map_err(|_| AudioError::new("Unsupported sampling rate"))?;
+    let delay = resampler.output_delay();
+
+    let failed = |_| AudioError::new("Failed to resample audio");
+    let mut output = resampler.process(&[samples], None).map_err(failed)?.remove(0);
+    // flush what is still inside the filter
+    let rest = resampler.process_partial(None::<&[Vec<f64>]>, None).map_err(failed)?;
+    output.extend_from_slice(&rest[0]);
+
+    Ok(output.into_iter().skip(delay).take(length).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_as_mono_at_output_rate() {
+        let path = std::env::temp_dir().join(format!("tohoku-tts-audio-{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        for _ in 0..4800 {
+            writer.write_sample(8000i16).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let samples = load(&path, 1.0).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples.len(), 2400);
+        // a constant signal stays constant away from the edges
+        assert!(samples[600..1800].iter().all(|s| (*s - 4000).abs() < 40), "{:?}", &samples[600..610]);
+    }
+
+    #[test]
+    fn resolves_only_inside_base() {
+        let base = std::env::temp_dir().join(format!("tohoku-tts-audio-dir-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("se")).unwrap();
+        std::fs::write(base.join("se").join("chime.wav"), b"").unwrap();
+        let outside = base.with_extension("wav");
+        std::fs::write(&outside, b"").unwrap();
+
+        let resolved = resolve(&base, Path::new("se/chime.wav"));
+        let absolute = resolve(&base, &outside);
+        let parent = resolve(&base, Path::new("se/../../tohoku-tts.wav"));
+        let escaping = resolve(&base.join("se"), Path::new("../se/chime.wav"));
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&outside, base.join("link.wav")).unwrap();
+            assert!(resolve(&base, Path::new("link.wav")).is_err());
+        }
+        std::fs::remove_dir_all(&base).unwrap();
+        std::fs::remove_file(&outside).unwrap();
+
+        assert!(resolved.unwrap().ends_with("se/chime.wav"));
+        assert!(absolute.is_err());
+        assert!(parent.is_err());
+        assert!(escaping.is_err());
+    }
+}
@@ -2,16 +2,22 @@
 pub(crate) mod audio_output;
 
 
-use tohoku_tts_voicevox::{self as tohoku, SynthesisVariant, SynthesisParams, SynthesisOptions, EngineHandle, TextSplitter};
+use tohoku_tts_voicevox::{self as tohoku, SynthesisVariant, SynthesisParams, SynthesisOptions, EngineHandle, TextSplitter, DialectProfile};
+use tohoku_tts_voicevox::dialect::{CentralVowelMerger, HumanizeParams, NorthernAccent, SouthernContour};
+use tohoku_tts_voicevox::{lexical, script, splitter, ssml};
+use tohoku_tts_voicevox::normalize::{self, NormalizeOptions};
+use tohoku_tts_voicevox::user_dict::{self, UserDict, UserWord};
 
 use std::io::Write;
 use std::io::Read;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub(crate) const PKG_NAME_JA: &str = "ジェネリック東北共通語読み上げソフト";
+pub(crate) const DEFAULT_USER_DICT: &str = "./user_dict.json";
 
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
@@ -20,6 +26,222 @@ pub enum OutputType {
     AudioDevice,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum SouthernContourMode {
+    Fixed,
+    Flat,
+    Rising,
+    Random,
+}
+
+/// Text normalization categories that can be switched off
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum NormalizeCategory {
+    Ruby,
+    Width,
+    Numbers,
+    Dates,
+    Times,
+    Currency,
+    Units,
+    PhoneNumbers,
+    Urls,
+}
+
+/// Switches for text normalization, shared by the synthesis commands
+#[derive(Debug, Args)]
+struct NormalizeArgs {
+    /// Leave the given categories as written (comma-separated)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    no_normalize: Vec<NormalizeCategory>,
+
+    /// Print the normalized text to stderr before synthesis
+    #[arg(long)]
+    show_normalized: bool,
+}
+
+impl NormalizeArgs {
+    fn options(&self) -> NormalizeOptions {
+        let mut options = NormalizeOptions::default();
+        for category in &self.no_normalize {
+            match category {
+                NormalizeCategory::Ruby => options.ruby = false,
+                NormalizeCategory::Width => options.width = false,
+                NormalizeCategory::Numbers => options.numbers = false,
+                NormalizeCategory::Dates => options.dates = false,
+                NormalizeCategory::Times => options.times = false,
+                NormalizeCategory::Currency => options.currency = false,
+                NormalizeCategory::Units => options.units = false,
+                NormalizeCategory::PhoneNumbers => options.phone_numbers = false,
+                NormalizeCategory::Urls => options.urls = false,
+            }
+        }
+        options
+    }
+
+    fn show(&self, text: &str) {
+        if self.show_normalized {
+            eprintln!("{}", normalize::normalize(text, &self.options()));
+        }
+    }
+}
+
+/// Switches for the optional dialect rules, shared by the synthesis commands
+#[derive(Debug, Args)]
+struct DialectArgs {
+    /// Dialect profile JSON file (defaults to the built-in profile of the variant)
+    #[arg(long)]
+    profile: Option<PathBuf>,
+
+    /// Insert a nasal onset before medial voiced stops (窓→マ~ド)
+    #[arg(long)]
+    prenasalization: bool,
+
+    /// Merge i/u after sibilants (シ/ス, ジ/ズ, チ/ツ) with the given strength
+    #[arg(long, value_enum)]
+    vowel_merger: Option<CentralVowelMerger>,
+
+    /// Casual speech: fuse ai/ae into a long e (ない→ねぇ)
+    #[arg(long)]
+    casual: bool,
+
+    /// Accent mapping for the northern variant
+    #[arg(long, value_enum)]
+    northern_accent: Option<NorthernAccent>,
+
+    /// Phrase contour for the southern variant
+    #[arg(long, value_enum)]
+    southern_contour: Option<SouthernContourMode>,
+
+    /// Rewrite standard forms into Tohoku ones before synthesis (だろう→だべ)
+    #[arg(long)]
+    lexical: bool,
+
+    /// Read quoted and bracketed parts of a sentence on their own, set apart by a short pause
+    #[arg(long)]
+    render_quotes: bool,
+
+    /// Add seeded jitter to pitch and lengths with the given strength (e.g. 1.0)
+    #[arg(long)]
+    humanize: Option<f64>,
+
+    /// Seed for randomized prosody
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+impl DialectArgs {
+    fn profile(&self, variant: SynthesisVariant) -> anyhow::Result<DialectProfile> {
+        let mut profile = match &self.profile {
+            Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+            None => variant.default_profile(),
+        };
+
+        if self.prenasalization {
+            profile.prenasalization.get_or_insert_with(Default::default);
+        }
+        if self.vowel_merger.is_some() {
+            profile.central_vowel_merger = self.vowel_merger;
+        }
+        if self.casual {
+            profile.vowel_coalescence.get_or_insert_with(Default::default);
+        }
+        if let Some(northern_accent) = self.northern_accent {
+            profile.northern_accent = northern_accent;
+        }
+        if let Some(southern_contour) = self.southern_contour {
+            profile.southern_contour = match southern_contour {
+                SouthernContourMode::Fixed => SouthernContour::default(),
+                SouthernContourMode::Flat => SouthernContour::flat(),
+                SouthernContourMode::Rising => SouthernContour::rising(),
+                SouthernContourMode::Random => SouthernContour::random(self.seed),
+            };
+        }
+        if self.lexical && profile.lexical_rules.is_none() {
+            profile.lexical_rules = Some(lexical::default_rules(variant));
+        }
+        if self.render_quotes {
+            profile.quotes.get_or_insert_with(Default::default);
+        }
+        if let Some(amount) = self.humanize {
+            if !(0.0..=HumanizeParams::AMOUNT_MAX).contains(&amount) {
+                anyhow::bail!("--humanize must be between 0 and {}", HumanizeParams::AMOUNT_MAX);
+            }
+            profile.humanize = Some(HumanizeParams {
+                amount,
+                seed: self.seed,
+                ..Default::default()
+            });
+        }
+        Ok(profile)
+    }
+}
+
+/// Voice, dialect and dictionary settings, shared by the synthesis commands
+#[derive(Debug, Args)]
+struct SynthesisArgs {
+    /// Dialect variant (of script characters that do not set one)
+    #[arg(long, value_enum)]
+    variant: SynthesisVariant,
+
+    /// Pitch offset
+    #[arg(long, default_value_t = SynthesisParams::default().pitch_offset())]
+    pitch_offset: f64,
+
+    /// Pitch range
+    #[arg(long, default_value_t = SynthesisParams::default().pitch_range())]
+    pitch_range: f64,
+
+    /// Speed scale
+    #[arg(long, default_value_t = SynthesisParams::default().speed_scale())]
+    speed_scale: f64,
+
+    #[command(flatten)]
+    dialect: DialectArgs,
+
+    #[command(flatten)]
+    normalize: NormalizeArgs,
+
+    /// Split sentences longer than this many characters at clause boundaries (0 disables)
+    #[arg(long, default_value_t = splitter::DEFAULT_MAX_LENGTH)]
+    max_sentence_length: usize,
+
+    /// Speaker ID (of script characters that do not set one)
+    #[arg(long, default_value = "2")]
+    speaker_id: u32,
+
+    /// User dictionary JSON file (ignored when missing)
+    #[arg(long, default_value = DEFAULT_USER_DICT)]
+    user_dict: PathBuf,
+
+    /// Do not use the bundled Tohoku place-name readings
+    #[arg(long)]
+    no_place_names: bool,
+}
+
+impl SynthesisArgs {
+    fn options(&self) -> anyhow::Result<SynthesisOptions> {
+        Ok(SynthesisOptions {
+            params: SynthesisParams::new(self.pitch_offset, self.pitch_range, self.speed_scale)?,
+            variant: self.variant,
+            profile: self.dialect.profile(self.variant)?,
+            speaker_id: self.speaker_id,
+            max_sentence_length: Some(self.max_sentence_length).filter(|max| *max > 0),
+            normalization: self.normalize.options(),
+            user_dict: None,
+            dialect_strength: 1.0,
+            audio_dir: None,
+        })
+    }
+
+    /// The bundled place names (unless disabled) with the user's words on top.
+    fn user_dict(&self) -> anyhow::Result<UserDict> {
+        let mut dict = if self.no_place_names { UserDict::new() } else { user_dict::place_names() };
+        dict.merge(load_user_dict(&self.user_dict)?)?;
+        Ok(dict)
+    }
+}
+
 #[derive(Debug, Parser)]
 #[command(about = PKG_NAME_JA, long_about = None, version)]
 struct Cli {
@@ -32,57 +254,77 @@ enum Command {
     /// One-shot synthesis
     #[command(arg_required_else_help = true)]
     TestSynthesis {
-        /// Dialect variant
-        #[arg(long, value_enum)]
-        variant: SynthesisVariant,
+        #[command(flatten)]
+        synthesis: SynthesisArgs,
 
-        /// Pitch offset
-        #[arg(long, default_value_t = SynthesisParams::default().pitch_offset())]
-        pitch_offset: f64,
+        /// Print which dialect rules changed each mora to stderr
+        #[arg(long)]
+        trace: bool,
 
-        /// Pitch range
-        #[arg(long, default_value_t = SynthesisParams::default().pitch_range())]
-        pitch_range: f64,
+        /// Print the text after normalization and lexical conversion and exit without synthesis
+        #[arg(long, conflicts_with = "speak_sample_text")]
+        dry_run: bool,
 
-        /// Speed scale
-        #[arg(long, default_value_t = SynthesisParams::default().speed_scale())]
-        speed_scale: f64,
+        /// Read the input as SSML
+        #[arg(long, conflicts_with_all = ["speak_sample_text", "dry_run", "trace"])]
+        ssml: bool,
+
+        /// Directory that SSML <audio> files are read from (without it, their fallback text is read)
+        #[arg(long, requires = "ssml")]
+        audio_dir: Option<PathBuf>,
 
         /// Speak the program version and sample text (ignores input)
         #[arg(long)]
         speak_sample_text: bool,
-
-        /// Speaker ID
-        #[arg(long, default_value = "2")]
-        speaker_id: u32,
     },
 
     /// Plays synthesized audio in one-shot mode
     #[command(arg_required_else_help = true)]
     PlaySynthesis {
-        /// Dialect variant
-        #[arg(long, value_enum)]
-        variant: SynthesisVariant,
+        #[command(flatten)]
+        synthesis: SynthesisArgs,
 
-        /// Pitch offset
-        #[arg(long, default_value_t = SynthesisParams::default().pitch_offset())]
-        pitch_offset: f64,
+        /// Print which dialect rules changed each mora to stderr
+        #[arg(long)]
+        trace: bool,
+
+        /// Print the text after normalization and lexical conversion and exit without synthesis
+        #[arg(long, conflicts_with = "speak_sample_text")]
+        dry_run: bool,
 
-        /// Pitch range
-        #[arg(long, default_value_t = SynthesisParams::default().pitch_range())]
-        pitch_range: f64,
+        /// Read the input as SSML
+        #[arg(long, conflicts_with_all = ["speak_sample_text", "dry_run", "trace"])]
+        ssml: bool,
 
-        /// Speed scale
-        #[arg(long, default_value_t = SynthesisParams::default().speed_scale())]
-        speed_scale: f64,
+        /// Directory that SSML <audio> files are read from (without it, their fallback text is read)
+        #[arg(long, requires = "ssml")]
+        audio_dir: Option<PathBuf>,
 
         /// Speak the program version and sample text (ignores input)
         #[arg(long)]
         speak_sample_text: bool,
+    },
 
-        /// Speaker ID
-        #[arg(long, default_value = "2")]
-        speaker_id: u32,
+    /// Renders a dialogue script into one WAV file
+    #[command(arg_required_else_help = true)]
+    RenderScript {
+        /// Script file (see the README for the format)
+        script: PathBuf,
+
+        /// Output WAV file (stdout if omitted)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Write the start and end of each line as JSON to this file
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+
+        /// Silence between lines in seconds, until the script sets @gap
+        #[arg(long, default_value_t = script::DEFAULT_GAP)]
+        gap: f64,
+
+        #[command(flatten)]
+        synthesis: SynthesisArgs,
     },
 
     /// Lixt speakers
@@ -92,6 +334,59 @@ enum Command {
         #[arg(long)]
         json: bool,
     },
+
+    /// Manage the user pronunciation dictionary
+    #[command(arg_required_else_help = true)]
+    Dict {
+        /// User dictionary JSON file
+        #[arg(long, default_value = DEFAULT_USER_DICT)]
+        file: PathBuf,
+
+        #[command(subcommand)]
+        command: DictCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum DictCommand {
+    /// Add a word, replacing any word with the same surface
+    Add {
+        /// Text as written
+        surface: String,
+
+        /// Reading in katakana
+        reading: String,
+
+        /// Mora after which the pitch falls (0 for unaccented)
+        #[arg(long, default_value_t = 0)]
+        accent: usize,
+
+        /// Higher priority wins over overlapping words (0-10)
+        #[arg(long, default_value_t = 5)]
+        priority: u8,
+    },
+
+    /// Remove a word
+    Remove {
+        surface: String,
+    },
+
+    /// List words
+    List {
+        /// JSON output
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Add every word of another dictionary file
+    Import {
+        path: PathBuf,
+    },
+
+    /// Write the dictionary to a file (stdout if omitted)
+    Export {
+        path: Option<PathBuf>,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
@@ -120,15 +415,52 @@ fn main() -> anyhow::Result<()> {
             }
         },
 
-        Command::TestSynthesis { variant, pitch_offset, pitch_range, speed_scale, speak_sample_text, speaker_id } => {
+        Command::Dict { file, command } => {
+            return manage_user_dict(&file, command);
+        },
+
+        Command::RenderScript { script, output, manifest, gap, synthesis } => {
+            let mut script = script::parse(&std::fs::read_to_string(&script)?, gap)
+                .map_err(|e| anyhow::anyhow!("{}: {}", script.display(), e))?;
+            let options = synthesis.options()?;
+
             log::info!("Initializing...");
             let dir = "./voicevox_core/open_jtalk_dic_utf_8-1.11";
             initialize(dir)?;
             log::info!("Initialized.");
+            let handle = EngineHandle::new()?;
+            handle.set_user_dict_blocking(synthesis.user_dict()?);
+            script.resolve_speakers(&get_speakers()?)?;
+
+            log::info!("Rendering {} lines...", script.lines.len());
+            let (wav, timings) = handle.render_script_blocking(script, options)?;
+
+            match output {
+                Some(path) => std::fs::write(path, wav)?,
+                None => std::io::stdout().write_all(wav.as_slice())?,
+            }
+            if let Some(path) = manifest {
+                let mut json = serde_json::to_string_pretty(&timings)?;
+                json.push('\n');
+                std::fs::write(path, json)?;
+            }
+        },
+
+        Command::TestSynthesis { synthesis, trace, dry_run, ssml, audio_dir, speak_sample_text } => {
+            if dry_run {
+                return print_converted_text(&synthesis.normalize.options(), &synthesis.dialect.profile(synthesis.variant)?);
+            }
+
+            log::info!("Initializing...");
+            let dir = "./voicevox_core/open_jtalk_dic_utf_8-1.11";
+            initialize(dir)?;
+            log::info!("Initialized.");
+            EngineHandle::new()?.set_user_dict_blocking(synthesis.user_dict()?);
 
             let speaker_list = get_speakers()?;
             let speaker_map = speaker_list.iter().map(|s| (s.speaker_id, s.clone())).collect::<HashMap<_, _>>();
 
+            let speaker_id = synthesis.speaker_id;
             let speaker_id = if speaker_map.contains_key(&speaker_id) {
                 speaker_id
             } else if speaker_map.contains_key(&2) {
@@ -147,33 +479,43 @@ fn main() -> anyhow::Result<()> {
             let speaker = format!("{}、スタイル {}", speaker.speaker_name, speaker.style_name);
             log::info!("Speaker: {}", &speaker);
 
-            let params = SynthesisParams::new(pitch_offset, pitch_range, speed_scale)?;
             let options = SynthesisOptions {
-                params,
-                variant,
                 speaker_id,
+                audio_dir,
+                ..synthesis.options()?
             };
 
-            let wav = if speak_sample_text {
-                let text = sample_text(variant, &speaker);
-                test_synthesis(options, &text)?
+            let text = if speak_sample_text {
+                sample_text(synthesis.variant, &speaker)
             } else {
                 let mut text = String::new();
                 let _ = std::io::stdin().read_to_string(&mut text)?;
-                test_synthesis(options, &text)?
+                text
+            };
+            let wav = if ssml {
+                ssml_synthesis(options, &text)?
+            } else {
+                synthesis.normalize.show(&text);
+                test_synthesis(options, &text, trace)?
             };
             std::io::stdout().write_all(wav.as_slice())?;
         },
 
-        Command::PlaySynthesis { variant, pitch_offset, pitch_range, speed_scale, speak_sample_text, speaker_id } => {
+        Command::PlaySynthesis { synthesis, trace, dry_run, ssml, audio_dir, speak_sample_text } => {
+            if dry_run {
+                return print_converted_text(&synthesis.normalize.options(), &synthesis.dialect.profile(synthesis.variant)?);
+            }
+
             log::info!("Initializing...");
             let dir = "./voicevox_core/open_jtalk_dic_utf_8-1.11";
             initialize(dir)?;
             log::info!("Initialized.");
+            EngineHandle::new()?.set_user_dict_blocking(synthesis.user_dict()?);
            
             let speaker_list = get_speakers()?;
             let speaker_map = speaker_list.iter().map(|s| (s.speaker_id, s.clone())).collect::<HashMap<_, _>>();
 
+            let speaker_id = synthesis.speaker_id;
             let speaker_id = if speaker_map.contains_key(&speaker_id) {
                 speaker_id
             } else if speaker_map.contains_key(&2) {
@@ -192,31 +534,46 @@ fn main() -> anyhow::Result<()> {
             let speaker = format!("{}、スタイル {}", speaker.speaker_name, speaker.style_name);
             log::info!("Speaker: {}", &speaker);
  
-            let params = SynthesisParams::new(pitch_offset, pitch_range, speed_scale)?;
             let options = SynthesisOptions {
-                params,
-                variant,
                 speaker_id,
+                audio_dir,
+                ..synthesis.options()?
             };
 
             let audio = audio_output::AudioPlayer::new()?;
-            let text_splitter = TextSplitter::new();
+            // over-long sentences are split (and joined with a short pause) by the engine
+            let text_splitter = TextSplitter::builder().max_length(None).build();
 
             let text = if speak_sample_text {
-                sample_text(variant, &speaker)
+                sample_text(synthesis.variant, &speaker)
             } else {
                 let mut text = String::new();
                 let _ = std::io::stdin().read_to_string(&mut text)?;
                 text
             };
 
-            let sentences = text_splitter.split_text(&text);
-
-            for sentence in sentences {
-                let wav = test_synthesis(options.clone(), &sentence)?;
-
-                log::info!("Requesting speech: {}", sentence);
+            if ssml {
+                let wav = ssml_synthesis(options, &text)?;
                 audio.play_wav(std::io::Cursor::new(wav))?;
+            } else {
+                synthesis.normalize.show(&text);
+                // rewrite the whole text before cutting it, so that no rule loses
+                // the context across a cut, and do not rewrite the sentences again
+                let text = convert_text(&text, &options.normalization, &options.profile);
+                let options = SynthesisOptions {
+                    normalization: NormalizeOptions::none(),
+                    profile: DialectProfile {
+                        lexical_rules: None,
+                        ..options.profile.clone()
+                    },
+                    ..options
+                };
+                for sentence in text_splitter.split_text(&text) {
+                    let wav = test_synthesis(options.clone(), &sentence, trace)?;
+
+                    log::info!("Requesting speech: {}", sentence);
+                    audio.play_wav(std::io::Cursor::new(wav))?;
+                }
             }
 
             log::info!("Waiting for audio to finish...");
@@ -235,14 +592,112 @@ fn initialize(dir: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn test_synthesis(options: SynthesisOptions, text: &str) -> anyhow::Result<Vec<u8>> {
+fn test_synthesis(options: SynthesisOptions, text: &str, trace: bool) -> anyhow::Result<Vec<u8>> {
     let handle = EngineHandle::new()?;
 
-    let wav = handle.synthesize_blocking(text.to_owned(), options)?;
+    if !trace {
+        let wav = handle.synthesize_blocking(text.to_owned(), options)?;
+        return Ok(wav);
+    }
+
+    let (wav, traces) = handle.synthesize_traced_blocking(text.to_owned(), options)?;
+    for sentence in traces {
+        eprintln!("# {}", sentence.text);
+        eprint!("{}", sentence.trace);
+    }
 
     Ok(wav)
 }
 
+fn ssml_synthesis(options: SynthesisOptions, text: &str) -> anyhow::Result<Vec<u8>> {
+    let document = ssml::parse(text)?;
+    let handle = EngineHandle::new()?;
+    let wav = handle.synthesize_ssml_blocking(document, options)?;
+    Ok(wav)
+}
+
+/// Normalization and lexical conversion, as the engine applies them before splitting.
+fn convert_text(text: &str, normalization: &NormalizeOptions, profile: &DialectProfile) -> String {
+    let text = normalize::normalize(text, normalization);
+    match &profile.lexical_rules {
+        Some(rules) => lexical::convert(&text, rules),
+        None => text,
+    }
+}
+
+/// Prints stdin after normalization and lexical conversion, one sentence per line.
+fn print_converted_text(normalization: &NormalizeOptions, profile: &DialectProfile) -> anyhow::Result<()> {
+    let mut text = String::new();
+    let _ = std::io::stdin().read_to_string(&mut text)?;
+    let text = convert_text(&text, normalization, profile);
+    for sentence in TextSplitter::new().split_text(&text) {
+        println!("{}", sentence);
+    }
+    Ok(())
+}
+
+/// Reads a user dictionary file; a missing file is an empty dictionary.
+fn load_user_dict(path: &Path) -> anyhow::Result<UserDict> {
+    if !path.exists() {
+        return Ok(UserDict::new());
+    }
+    let dict: UserDict = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    dict.validate().map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    Ok(dict)
+}
+
+fn user_dict_json(dict: &UserDict) -> anyhow::Result<String> {
+    let mut json = serde_json::to_string_pretty(dict)?;
+    json.push('\n');
+    Ok(json)
+}
+
+fn manage_user_dict(file: &Path, command: DictCommand) -> anyhow::Result<()> {
+    let mut dict = load_user_dict(file)?;
+
+    match command {
+        DictCommand::Add { surface, reading, accent, priority } => {
+            let word = UserWord::new(&surface, &reading, accent, priority)?;
+            if let Some(old) = dict.add(word)? {
+                log::info!("Replaced {} ({})", old.surface, old.reading);
+            }
+        },
+        DictCommand::Remove { surface } => {
+            if dict.remove(&surface).is_none() {
+                anyhow::bail!("{} is not in the dictionary", surface);
+            }
+        },
+        DictCommand::List { json } => {
+            if json {
+                std::io::stdout().write_all(user_dict_json(&dict)?.as_bytes())?;
+            } else {
+                println!("SURFACE\tREADING\tACCENT\tPRIORITY");
+                for word in dict.words() {
+                    println!("{}\t{}\t{}\t{}", word.surface, word.reading, word.accent, word.priority);
+                }
+            }
+            return Ok(());
+        },
+        DictCommand::Import { path } => {
+            let other: UserDict = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            let count = other.words().len();
+            dict.merge(other).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+            log::info!("Imported {} words", count);
+        },
+        DictCommand::Export { path } => {
+            let json = user_dict_json(&dict)?;
+            match path {
+                Some(path) => std::fs::write(path, json)?,
+                None => std::io::stdout().write_all(json.as_bytes())?,
+            }
+            return Ok(());
+        },
+    }
+
+    std::fs::write(file, user_dict_json(&dict)?)?;
+    Ok(())
+}
+
 fn get_speakers() -> anyhow::Result<Vec<tohoku::Speaker>> {
     let handle = EngineHandle::new()?;
     let speakers = handle.get_speakers_blocking()?;
@@ -0,0 +1,1418 @@
+use serde::{Deserialize, Serialize};
+use clap::ValueEnum;
+
+use std::ops::Range;
+
+use crate::types::{self, Consonant, Manner, Vowel};
+use crate::kana;
+use crate::rng::SplitMix64;
+use crate::trace::Tracer;
+use crate::lexical::LexicalRule;
+
+/// Per-variant switches and parameters for the optional dialect rules.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DialectProfile {
+    /// Nasal onset before medial voiced stops (窓→マ~ド, 鍵→カ~ギ).
+    /// `None` disables the rule.
+    pub prenasalization: Option<PrenasalizationParams>,
+
+    /// Merger of i/u after sibilants (ズーズー弁). `None` disables the rule.
+    pub central_vowel_merger: Option<CentralVowelMerger>,
+
+    /// Casual fusion of a+i / a+e into a long e (ない→ねぇ, 高い→たけぇ).
+    /// `None` disables the rule.
+    pub vowel_coalescence: Option<VowelCoalescenceParams>,
+
+    /// Mapping from the Tokyo accent nucleus to the pitch peak (Northern variant only).
+    #[serde(default)]
+    pub northern_accent: NorthernAccent,
+
+    /// Pitch contour of each accent phrase (Southern variant only).
+    #[serde(default)]
+    pub southern_contour: SouthernContour,
+
+    /// Downward drift of phrase pitch across a sentence. `None` keeps every
+    /// phrase at its own average pitch.
+    pub declination: Option<DeclinationParams>,
+
+    /// Apply pitch factors relative to the measured baseline of each speaker
+    /// rather than to raw log-F0 values.
+    #[serde(default)]
+    pub speaker_calibration: bool,
+
+    /// Tell yes/no, wh- and particle questions apart and give each its own
+    /// contour. `None` treats every interrogative phrase as a yes/no question
+    /// with the variant's built-in rise.
+    pub questions: Option<QuestionContours>,
+
+    /// Sentence-final particles with their own pitch and length. A matching
+    /// particle takes precedence over the question contour.
+    #[serde(default)]
+    pub final_particles: Vec<FinalParticle>,
+
+    /// Seeded jitter on pitch and lengths. `None` keeps the output exact.
+    pub humanize: Option<HumanizeParams>,
+
+    /// Substitutions applied to the input text before it is split into
+    /// sentences (だろう→だべ). `None` leaves the text as written.
+    pub lexical_rules: Option<Vec<LexicalRule>>,
+
+    /// Render bracketed and quoted parts of a sentence as their own
+    /// utterances. `None` reads each sentence as a whole.
+    pub quotes: Option<QuoteStyle>,
+}
+
+impl DialectProfile {
+    pub fn northern() -> Self {
+        Self {
+            prenasalization: None,
+            central_vowel_merger: None,
+            vowel_coalescence: None,
+            northern_accent: NorthernAccent::default(),
+            southern_contour: SouthernContour::default(),
+            declination: Some(DeclinationParams {
+                downstep: Some(0.99),
+                ..Default::default()
+            }),
+            speaker_calibration: true,
+            questions: Some(QuestionContours::northern()),
+            final_particles: FinalParticle::defaults(),
+            humanize: None,
+            lexical_rules: None,
+            quotes: None,
+        }
+    }
+
+    pub fn southern() -> Self {
+        Self {
+            prenasalization: None,
+            central_vowel_merger: None,
+            vowel_coalescence: None,
+            northern_accent: NorthernAccent::default(),
+            southern_contour: SouthernContour::default(),
+            declination: Some(DeclinationParams::default()),
+            speaker_calibration: true,
+            questions: Some(QuestionContours::southern()),
+            final_particles: FinalParticle::defaults(),
+            humanize: None,
+            lexical_rules: None,
+            quotes: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrenasalizationParams {
+    /// Length of the inserted `N` segment, in seconds
+    pub nasal_length: f64,
+
+    /// Factor applied to the vowel length of the mora before the nasal
+    pub vowel_length_scale: f64,
+
+    /// Also apply to stops produced by the voicing rule (k→g, t→d)
+    pub include_derived: bool,
+}
+
+impl Default for PrenasalizationParams {
+    fn default() -> Self {
+        Self {
+            nasal_length: 0.04,
+            vowel_length_scale: 0.8,
+            include_derived: true,
+        }
+    }
+}
+
+/// Inserts a short `N` mora before medial voiced stops.
+///
+/// `original` is the phrase as returned by `audio_query`, used to tell
+/// lexical voiced stops from the ones produced by the voicing rule.
+/// The accent index is shifted when the nasal lands before the nucleus.
+pub(crate) fn apply_prenasalization(phrase: &mut types::AccentPhrase, original: &types::AccentPhrase, params: &PrenasalizationParams, index: usize, tracer: &mut Tracer) {
+    let mut moras = Vec::with_capacity(phrase.moras.len());
+    let mut accent = phrase.accent;
+
+    let voiced_stop = |c: Consonant| c.is_voiced() && c.manner() == Manner::Stop;
+
+    for (j, mora) in phrase.moras.iter().enumerate() {
+        let is_voiced_stop = mora.consonant.is_some_and(voiced_stop);
+        let is_lexical = original.moras.get(j)
+            .and_then(|m| m.consonant)
+            .is_some_and(voiced_stop);
+
+        let prev: Option<&mut types::Mora> = moras.last_mut();
+        let insert = j > 0 && is_voiced_stop && (is_lexical || params.include_derived) && match &prev {
+            // only after a voiced vowel; N, cl and devoiced vowels have no room for a nasal
+            Some(prev) => prev.vowel.is_vowel() && !prev.vowel.is_devoiced(),
+            None => false,
+        };
+
+        if insert {
+            let prev = prev.unwrap();
+            prev.vowel_length *= params.vowel_length_scale;
+            let pitch = prev.pitch;
+            tracer.fire(index, moras.len() - 1, "prenasalization");
+            moras.push(types::Mora {
+                text: "ン".to_string(),
+                vowel: Vowel::N,
+                vowel_length: params.nasal_length,
+                pitch,
+                consonant: None,
+                consonant_length: None,
+            });
+            tracer.insert(index, moras.len() - 1, "prenasalization");
+
+            // accent is 1-indexed; a nasal inserted at or before the nucleus shifts it
+            if (moras.len() as i32) <= accent {
+                accent += 1;
+            }
+        }
+
+        moras.push(mora.clone());
+    }
+
+    phrase.moras = moras;
+    phrase.accent = accent;
+}
+
+/// Strength of the central vowel merger.
+///
+/// VOICEVOX has no central vowel, so affected moras are rewritten to
+/// their /u/ counterparts; higher grades merge more consonant series.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum CentralVowelMerger {
+    /// シ→ス
+    Weak,
+
+    /// シ→ス, ジ→ズ
+    Medium,
+
+    /// シ→ス, ジ→ズ, チ→ツ
+    Strong,
+}
+
+impl CentralVowelMerger {
+    fn merge(&self, consonant: Consonant) -> Option<Consonant> {
+        match consonant {
+            Consonant::Sh => Some(Consonant::S),
+            Consonant::J if *self >= Self::Medium => Some(Consonant::Z),
+            Consonant::Ch if *self >= Self::Strong => Some(Consonant::Ts),
+            _ => None,
+        }
+    }
+}
+
+/// Rewrites sibilant + i moras to sibilant + u, keeping devoicing.
+pub(crate) fn apply_central_vowel_merger(phrase: &mut types::AccentPhrase, merger: CentralVowelMerger, index: usize, tracer: &mut Tracer) {
+    for (j, mora) in phrase.moras.iter_mut().enumerate() {
+        let vowel = match mora.vowel {
+            Vowel::I => Vowel::U,
+            Vowel::UnvoicedI => Vowel::UnvoicedU,
+            _ => continue,
+        };
+        let Some(consonant) = mora.consonant.and_then(|c| merger.merge(c)) else {
+            continue;
+        };
+        mora.consonant = Some(consonant);
+        mora.vowel = vowel;
+        tracer.fire(index, j, "central_vowel_merger");
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VowelCoalescenceParams {
+    /// Factor applied to the summed vowel lengths of the fused moras
+    pub length_scale: f64,
+}
+
+impl Default for VowelCoalescenceParams {
+    fn default() -> Self {
+        Self {
+            length_scale: 1.0,
+        }
+    }
+}
+
+/// Fuses a voiced /a/ mora with a following bare /i/ or /e/ mora into one long /e/.
+///
+/// The second mora is dropped and the accent index is moved so that it
+/// still points at the same (or the fused) mora.
+pub(crate) fn apply_vowel_coalescence(phrase: &mut types::AccentPhrase, params: &VowelCoalescenceParams, index: usize, tracer: &mut Tracer) {
+    let mut j = 0;
+    while j + 1 < phrase.moras.len() {
+        let next = &phrase.moras[j + 1];
+        let fuses = phrase.moras[j].vowel == Vowel::A
+            && next.consonant.is_none()
+            && (next.vowel == Vowel::I || next.vowel == Vowel::E);
+        // e.g. ヤ+イ: there is no /ye/ mora to fuse into (イェ is a loanword sound)
+        let consonant = phrase.moras[j].consonant;
+        let has_e = consonant != Some(Consonant::Y)
+            && kana::mora_to_text(consonant, Vowel::E).is_some_and(|text| !text.is_empty());
+        if !(fuses && has_e) {
+            j += 1;
+            continue;
+        }
+
+        let next = phrase.moras.remove(j + 1);
+        tracer.fire(index, j, "vowel_coalescence");
+        tracer.remove(index, j + 1, "vowel_coalescence");
+        let mora = &mut phrase.moras[j];
+        mora.vowel = Vowel::E;
+        mora.vowel_length = (mora.vowel_length + next.vowel_length) * params.length_scale;
+
+        // accent is 1-indexed; the removed mora was at position j + 2
+        if phrase.accent >= j as i32 + 2 {
+            phrase.accent -= 1;
+        }
+        j += 1;
+    }
+}
+
+/// How the Northern variant places the pitch peak of an accent phrase.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum NorthernAccent {
+    /// 北奥羽 mapping: the peak is on the syllable after the Tokyo nucleus.
+    ///
+    /// Special moras (`N`, `cl`, long vowels) never carry the peak on their
+    /// own and share it with the head of their syllable. Unaccented and
+    /// final-accented phrases peak on the last syllable.
+    #[default]
+    Shifted,
+
+    /// Peak on the Tokyo nucleus itself (the original mapping).
+    Legacy,
+}
+
+/// Whether mora `k` lengthens the vowel of the mora before it.
+///
+/// VOICEVOX spells long vowels with the bare vowel (トオ, ネエ), never `ー`,
+/// so this compares vowels, ignoring devoicing.
+pub(crate) fn is_long_vowel(moras: &[types::Mora], k: usize) -> bool {
+    let mora = &moras[k];
+    k > 0 && mora.consonant.is_none() && mora.vowel.is_vowel() && mora.vowel.voiced() == moras[k - 1].vowel.voiced()
+}
+
+/// `N`, `cl` and long vowels cannot start a syllable.
+fn is_special_mora(moras: &[types::Mora], k: usize) -> bool {
+    let mora = &moras[k];
+    (mora.consonant.is_none() && (mora.vowel == Vowel::N || mora.vowel == Vowel::Cl)) || is_long_vowel(moras, k)
+}
+
+/// Returns the range of moras that carry the pitch peak of a Northern phrase.
+pub(crate) fn northern_accent_peak(phrase: &types::AccentPhrase, mapping: NorthernAccent) -> Range<usize> {
+    let len = phrase.moras.len();
+    // accent is 1-indexed
+    let nucleus = (phrase.accent.max(1) as usize - 1).min(len.saturating_sub(1));
+
+    match mapping {
+        NorthernAccent::Legacy => nucleus..nucleus + 1,
+        NorthernAccent::Shifted => {
+            let syllable_end = |start: usize| {
+                (start + 1..len).find(|&k| !is_special_mora(&phrase.moras, k)).unwrap_or(len)
+            };
+            let last_syllable = (0..len).rev().find(|&k| k == 0 || !is_special_mora(&phrase.moras, k)).unwrap_or(0);
+
+            let next_syllable = syllable_end(nucleus);
+            let start = if next_syllable < len {
+                next_syllable
+            } else {
+                last_syllable
+            };
+            start..syllable_end(start)
+        },
+    }
+}
+
+/// Pitch factors of the Southern phrase shape, relative to the phrase average.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SouthernShape {
+    /// First mora
+    pub first: f64,
+
+    /// Moras between the first one and the peak
+    pub middle: f64,
+
+    /// Peak mora
+    pub peak: f64,
+
+    /// Moras after the peak
+    pub after: f64,
+}
+
+impl Default for SouthernShape {
+    fn default() -> Self {
+        Self {
+            first: 0.96,
+            middle: 1.03,
+            peak: 1.04,
+            after: 0.95,
+        }
+    }
+}
+
+/// How the Southern (無アクセント) variant shapes each accent phrase.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SouthernContour {
+    /// One fixed shape with the peak on the last mora (the original behaviour).
+    Fixed(SouthernShape),
+
+    /// Every mora at the same level.
+    Flat {
+        level: f64,
+    },
+
+    /// Linear rise from the first to the last mora.
+    Rising {
+        start: f64,
+        end: f64,
+    },
+
+    /// Fixed shape whose peak is moved up to `max_shift` moras earlier,
+    /// chosen per phrase from `seed`.
+    Random {
+        shape: SouthernShape,
+        max_shift: usize,
+        seed: u64,
+    },
+}
+
+impl Default for SouthernContour {
+    fn default() -> Self {
+        Self::Fixed(SouthernShape::default())
+    }
+}
+
+impl SouthernContour {
+    pub fn flat() -> Self {
+        Self::Flat {
+            level: 1.0,
+        }
+    }
+
+    pub fn rising() -> Self {
+        Self::Rising {
+            start: 0.97,
+            end: 1.06,
+        }
+    }
+
+    pub fn random(seed: u64) -> Self {
+        Self::Random {
+            shape: SouthernShape::default(),
+            max_shift: 2,
+            seed,
+        }
+    }
+
+    /// Seeded generator for one utterance; only the `Random` mode draws from it.
+    pub(crate) fn rng(&self) -> SplitMix64 {
+        match self {
+            Self::Random { seed, .. } => SplitMix64::new(*seed),
+            _ => SplitMix64::new(0),
+        }
+    }
+
+    /// Returns the pitch factor for each of `len` moras of a phrase.
+    pub(crate) fn factors(&self, len: usize, rng: &mut SplitMix64) -> Vec<f64> {
+        let shaped = |shape: &SouthernShape, peak: usize| {
+            (0..len).map(|j| {
+                if j == 0 {
+                    shape.first
+                } else if j < peak {
+                    shape.middle
+                } else if j == peak {
+                    shape.peak
+                } else {
+                    shape.after
+                }
+            }).collect()
+        };
+
+        let last = len.saturating_sub(1);
+        match self {
+            Self::Fixed(shape) => shaped(shape, last),
+            Self::Flat { level } => vec![*level; len],
+            Self::Rising { start, end } => (0..len).map(|j| {
+                if last == 0 {
+                    *end
+                } else {
+                    start + (end - start) * j as f64 / last as f64
+                }
+            }).collect(),
+            Self::Random { shape, max_shift, .. } => {
+                // keep the peak off the first mora so the initial rise survives
+                let shift = rng.next_below_or_eq((*max_shift).min(last.saturating_sub(1)));
+                shaped(shape, last - shift)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeclinationParams {
+    /// Pitch lowering per accent phrase, as a fraction of the phrase pitch
+    pub slope: f64,
+
+    /// Start again from the top after a pause (`pause_mora`)
+    pub reset_on_pause: bool,
+
+    /// Extra factor applied to every phrase following an accented one
+    pub downstep: Option<f64>,
+}
+
+impl Default for DeclinationParams {
+    fn default() -> Self {
+        Self {
+            slope: 0.006,
+            reset_on_pause: true,
+            downstep: None,
+        }
+    }
+}
+
+/// Returns the pitch factor of each accent phrase of an utterance.
+///
+/// `accent_phrases` should be the phrases as returned by `audio_query`, so that
+/// accented phrases (nucleus before the last mora) can be told apart.
+pub(crate) fn declination_factors(accent_phrases: &[types::AccentPhrase], params: &DeclinationParams) -> Vec<f64> {
+    let mut factors = Vec::with_capacity(accent_phrases.len());
+    let mut position = 0;
+    let mut downstep = 1.0;
+    for phrase in accent_phrases {
+        factors.push((1.0 - params.slope * position as f64).max(0.0) * downstep);
+
+        position += 1;
+        if let Some(step) = params.downstep {
+            let accented = phrase.accent >= 1 && (phrase.accent as usize) < phrase.moras.len();
+            if accented {
+                downstep *= step;
+            }
+        }
+        if params.reset_on_pause && phrase.pause_mora.is_some() {
+            position = 0;
+            downstep = 1.0;
+        }
+    }
+    factors
+}
+
+/// Log-F0 baseline of a speaker, measured from a calibration sentence.
+///
+/// `Mora.pitch` is a log-F0 value, so multiplying it by a fixed factor gives
+/// a wider interval for high voices than for low ones. Factors are instead
+/// applied after moving the speaker's register onto the reference voice the
+/// rules were tuned with, so a factor means the same number of semitones
+/// for every speaker.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeakerPitch {
+    /// Mean log-F0 of voiced moras
+    pub mean: f64,
+}
+
+impl SpeakerPitch {
+    /// Approximate mean log-F0 of the default speaker (ID 2) the rule factors were tuned with
+    pub const REFERENCE_MEAN: f64 = 5.8;
+
+    /// Baseline of the reference voice; `scale` is then a plain multiplication.
+    pub fn reference() -> Self {
+        Self {
+            mean: Self::REFERENCE_MEAN,
+        }
+    }
+
+    /// Measures voiced moras of a query; `None` if it has none.
+    pub fn from_query(query: &types::AudioQuery) -> Option<Self> {
+        let pitches = query.accent_phrases.iter()
+            .flat_map(|phrase| phrase.moras.iter())
+            .map(|mora| mora.pitch)
+            .filter(|pitch| *pitch > 0.0)
+            .collect::<Vec<f64>>();
+        if pitches.is_empty() {
+            return None;
+        }
+        Some(Self {
+            mean: pitches.iter().sum::<f64>() / pitches.len() as f64,
+        })
+    }
+
+    /// Applies a rule factor to `pitch` as if it were spoken by the reference voice.
+    pub fn scale(&self, pitch: f64, factor: f64) -> f64 {
+        let shift = Self::REFERENCE_MEAN - self.mean;
+        (pitch + shift) * factor - shift
+    }
+}
+
+/// Kind of question, which decides the contour at the end of an utterance.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuestionType {
+    /// Question without a question word or final particle (行く？)
+    YesNo,
+
+    /// Question containing a question word (どこさ行ぐの？, 何ですか)
+    Wh,
+
+    /// Question ending in a particle (〜すか, 〜だべ, 〜け, 〜が)
+    Particle,
+}
+
+/// Pitch and length changes at the end of a question.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuestionContour {
+    /// Pitch factor of the mora before the last voiced mora
+    pub preceding: f64,
+
+    /// Pitch factor of the last voiced mora
+    pub last: f64,
+
+    /// Vowel length factor of the last voiced mora
+    pub last_length: f64,
+}
+
+/// Contour of each question type.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuestionContours {
+    pub yes_no: QuestionContour,
+    pub wh: QuestionContour,
+    pub particle: QuestionContour,
+}
+
+impl QuestionContours {
+    /// Yes/no questions rise slightly; questions with a question word or a
+    /// particle fall, the particle ones from a raised preceding mora.
+    pub fn northern() -> Self {
+        Self {
+            yes_no: QuestionContour { preceding: 1.0, last: 1.02, last_length: 1.0 },
+            wh: QuestionContour { preceding: 1.0, last: 0.96, last_length: 1.0 },
+            particle: QuestionContour { preceding: 1.03, last: 0.97, last_length: 1.1 },
+        }
+    }
+
+    pub fn southern() -> Self {
+        Self {
+            yes_no: QuestionContour { preceding: 1.0, last: 1.04, last_length: 1.0 },
+            wh: QuestionContour { preceding: 1.0, last: 1.0, last_length: 1.0 },
+            particle: QuestionContour { preceding: 1.04, last: 0.98, last_length: 1.1 },
+        }
+    }
+
+    pub fn get(&self, question: QuestionType) -> &QuestionContour {
+        match question {
+            QuestionType::YesNo => &self.yes_no,
+            QuestionType::Wh => &self.wh,
+            QuestionType::Particle => &self.particle,
+        }
+    }
+}
+
+/// Readings of question words, matched as the first word of an accent phrase
+const QUESTION_WORDS: &[&str] = &[
+    "ナニ", "ナン", "ナンジ", "ナンニン", "ナンニチ", "ナンカイ", "ナンサイ", "ナンバン", "ナンネン",
+    "ナンボ", "ナゼ", "ドコ", "ドレ", "ドノ", "ドチラ", "ドッチ", "ドナタ", "ドンナ", "ドオ",
+    "ドオシテ", "ドオヤッテ", "ダレ", "イツ", "イクラ", "イクツ",
+];
+
+/// Particles and copulas that may follow a question word in the same accent phrase
+const QUESTION_WORD_FOLLOWERS: &[&str] = &[
+    "ガ", "ヲ", "ニ", "デ", "ト", "ワ", "ノ", "サ", "エ", "カラ", "マデ", "ダ", "ジャ", "ヨ", "ネ", "ベ",
+];
+
+/// Final particles that mark a question when the phrase is interrogative
+const QUESTION_PARTICLES: &[&str] = &["カ", "ベ", "ケ", "ガ"];
+
+fn phrase_text(phrase: &types::AccentPhrase) -> String {
+    phrase.moras.iter().map(|mora| mora.text.as_str()).collect()
+}
+
+/// Text of a phrase without its trailing long vowels (イッタッケエ → イッタッケ).
+fn phrase_text_without_long_vowels(phrase: &types::AccentPhrase) -> String {
+    let mut end = phrase.moras.len();
+    while end > 0 && is_long_vowel(&phrase.moras, end - 1) {
+        end -= 1;
+    }
+    phrase.moras[..end].iter().map(|mora| mora.text.as_str()).collect()
+}
+
+/// Whether an accent phrase starts with a question word.
+///
+/// The word must make up the whole phrase or be followed by a particle, so
+/// that 南部 (ナンブ) or ドレス are not mistaken for 何 or どれ. Indefinites
+/// (何も, 誰でも) and, except at the end, 何か/誰か are not questions.
+fn has_question_word(phrase: &types::AccentPhrase, is_last: bool) -> bool {
+    let text = phrase_text(phrase);
+    if text.ends_with('モ') {
+        return false;
+    }
+    QUESTION_WORDS.iter().any(|word| {
+        text.strip_prefix(word).is_some_and(|rest| {
+            rest.is_empty()
+                || QUESTION_WORD_FOLLOWERS.iter().any(|follower| rest.starts_with(follower))
+                || (rest.starts_with('カ') && is_last)
+        })
+    })
+}
+
+/// Classifies the intonation unit ending with the last of `phrases`.
+///
+/// Works on the readings in the query: a question word anywhere in the unit
+/// makes a wh-question, otherwise a final particle makes a particle question,
+/// otherwise the `is_interrogative` flag makes a yes/no question. 〜ですか and
+/// 〜ますか count as questions even without a question mark.
+pub fn classify_question(phrases: &[types::AccentPhrase]) -> Option<QuestionType> {
+    let last = phrases.last()?;
+    let ending = phrase_text_without_long_vowels(last);
+    let particle = (last.is_interrogative && QUESTION_PARTICLES.iter().any(|p| ending.ends_with(p)))
+        || ending.ends_with("スカ");
+    if !last.is_interrogative && !particle {
+        return None;
+    }
+    let wh = phrases.iter().enumerate().any(|(i, phrase)| has_question_word(phrase, i + 1 == phrases.len()));
+    Some(if wh {
+        QuestionType::Wh
+    } else if particle {
+        QuestionType::Particle
+    } else {
+        QuestionType::YesNo
+    })
+}
+
+/// Whether accent phrase `index` ends an intonation unit: it is followed by
+/// a long pause or ends the utterance.
+pub(crate) fn ends_intonation_unit(accent_phrases: &[types::AccentPhrase], index: usize) -> bool {
+    match &accent_phrases[index].pause_mora {
+        Some(mora) => mora.vowel == Vowel::Pau && mora.vowel_length >= 0.3,
+        None => index == accent_phrases.len() - 1,
+    }
+}
+
+/// Returns the question type of each accent phrase that ends a question.
+///
+/// With `classify` unset, only the `is_interrogative` flag is used and every
+/// question is a yes/no question.
+pub(crate) fn question_types(accent_phrases: &[types::AccentPhrase], classify: bool) -> Vec<Option<QuestionType>> {
+    let mut start = 0;
+    (0..accent_phrases.len()).map(|i| {
+        if !ends_intonation_unit(accent_phrases, i) {
+            return None;
+        }
+        let unit = &accent_phrases[start..=i];
+        start = i + 1;
+        if classify {
+            classify_question(unit)
+        } else {
+            accent_phrases[i].is_interrogative.then_some(QuestionType::YesNo)
+        }
+    }).collect()
+}
+
+/// Applies a question contour to the end of a phrase.
+pub(crate) fn apply_question_contour(phrase: &mut types::AccentPhrase, question: QuestionType, contour: &QuestionContour, pitch: &SpeakerPitch, index: usize, tracer: &mut Tracer) {
+    let rule = match question {
+        QuestionType::YesNo => "yes_no_question",
+        QuestionType::Wh => "wh_question",
+        QuestionType::Particle => "particle_question",
+    };
+    let Some(last) = phrase.moras.iter().rposition(|mora| !mora.vowel.is_devoiced()) else {
+        return;
+    };
+    if last > 0 && contour.preceding != 1.0 {
+        let mora = &mut phrase.moras[last - 1];
+        mora.pitch = pitch.scale(mora.pitch, contour.preceding);
+        tracer.fire(index, last - 1, rule);
+    }
+    let mora = &mut phrase.moras[last];
+    mora.pitch = pitch.scale(mora.pitch, contour.last);
+    mora.vowel_length *= contour.last_length;
+    tracer.fire(index, last, rule);
+}
+
+/// Prosody of a sentence-final particle, replacing the generic final
+/// lengthening and contour on the moras it covers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FinalParticle {
+    /// Reading in katakana, matched against the end of an intonation unit.
+    /// Long vowels after it are covered as well (ねえ, なやー).
+    pub reading: String,
+
+    /// Match only questions (`true`) or only statements (`false`); `None` matches both
+    #[serde(default)]
+    pub question: Option<bool>,
+
+    /// Pitch factor of the first mora of the particle
+    pub pitch_start: f64,
+
+    /// Pitch factor of the last mora of the particle, interpolated in between
+    pub pitch_end: f64,
+
+    /// Vowel length factor of every mora of the particle
+    pub length: f64,
+}
+
+impl FinalParticle {
+    fn new(reading: &str, question: Option<bool>, pitch_start: f64, pitch_end: f64, length: f64) -> Self {
+        Self {
+            reading: reading.to_string(),
+            question,
+            pitch_start,
+            pitch_end,
+            length,
+        }
+    }
+
+    /// Built-in table: drawn-out falls for statements, a rise for 〜だべ?
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("ネ", None, 1.01, 0.97, 1.5),
+            Self::new("ナ", None, 1.0, 0.95, 1.5),
+            Self::new("ヤ", None, 1.0, 0.95, 1.4),
+            Self::new("ナヤ", None, 1.02, 0.93, 1.5),
+            Self::new("ベ", Some(false), 1.02, 0.95, 1.3),
+            Self::new("ベ", Some(true), 1.0, 1.05, 1.3),
+            Self::new("ダベ", Some(false), 1.03, 0.95, 1.3),
+            Self::new("ダベ", Some(true), 1.0, 1.06, 1.3),
+            Self::new("ッチャ", None, 1.03, 0.96, 1.3),
+        ]
+    }
+}
+
+/// Characters dropped from the end of a clause before its final particle
+/// is compared: punctuation, closing brackets and drawn-out vowels (ねえ, なや〜).
+const CLAUSE_TRAILERS: &str = "、。，,．.！!？?…‥」』）)]〕 \u{3000}\tーｰ〜～~ぁぃぅぇぉァィゥェォあいうえおアイウエオ";
+
+/// Splits a sentence at its punctuation and returns each clause in katakana,
+/// trimmed by `CLAUSE_TRAILERS` (そうだべえ！ → ソウダベ).
+fn clause_endings(text: &str) -> Vec<String> {
+    text.split(['、', '。', '，', ',', '！', '!', '？', '?'])
+        .map(|clause| {
+            clause.trim_end_matches(|c| CLAUSE_TRAILERS.contains(c)).chars().map(|c| match c {
+                'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+                _ => c,
+            }).collect::<String>()
+        })
+        .filter(|clause| !clause.is_empty())
+        .collect()
+}
+
+/// Finds the final particle of each accent phrase that ends an intonation
+/// unit, as the number of moras it covers and the matching table entry.
+///
+/// The longest reading wins; between equal readings, an entry restricted to
+/// questions or statements wins over one that matches both.
+///
+/// With the sentence `text`, a particle must also be written in kana at the
+/// end of its clause, so that 魚 (サカナ) or 鍋 (ナベ) do not end in ナ or ベ.
+/// Without it, single-mora particles are not matched at all.
+pub(crate) fn final_particles<'a>(accent_phrases: &[types::AccentPhrase], table: &'a [FinalParticle], questions: &[Option<QuestionType>], text: Option<&str>) -> Vec<Option<(usize, &'a FinalParticle)>> {
+    let units = (0..accent_phrases.len()).filter(|&i| ends_intonation_unit(accent_phrases, i)).count();
+    let clauses = text.map(clause_endings).unwrap_or_default();
+    let mut unit = 0;
+    accent_phrases.iter().enumerate().map(|(i, phrase)| {
+        if !ends_intonation_unit(accent_phrases, i) {
+            return None;
+        }
+        unit += 1;
+        // clauses line up with the units when VOICEVOX paused at every mark;
+        // otherwise only the end of the sentence is known
+        let clause = text.map(|_| {
+            if clauses.len() == units {
+                clauses[unit - 1].as_str()
+            } else if unit == units {
+                clauses.last().map_or("", String::as_str)
+            } else {
+                ""
+            }
+        });
+        let is_question = questions[i].is_some();
+        let mut long_vowels = 0;
+        while long_vowels < phrase.moras.len() && is_long_vowel(&phrase.moras, phrase.moras.len() - 1 - long_vowels) {
+            long_vowels += 1;
+        }
+        let body = &phrase.moras[..phrase.moras.len() - long_vowels];
+        table.iter()
+            .filter(|particle| particle.question.is_none_or(|question| question == is_question))
+            .filter(|particle| clause.is_none_or(|clause| clause.ends_with(particle.reading.as_str())))
+            .filter_map(|particle| {
+                let mut rest = particle.reading.as_str();
+                let mut count = 0;
+                for mora in body.iter().rev() {
+                    if rest.is_empty() {
+                        break;
+                    }
+                    rest = rest.strip_suffix(mora.text.as_str())?;
+                    count += 1;
+                }
+                (rest.is_empty() && count > 0 && (count > 1 || clause.is_some())).then_some((count + long_vowels, particle))
+            })
+            .max_by_key(|(_, particle)| (particle.reading.chars().count(), particle.question.is_some()))
+    }).collect()
+}
+
+/// Applies a final particle to the last `len` moras of a phrase.
+pub(crate) fn apply_final_particle(phrase: &mut types::AccentPhrase, len: usize, particle: &FinalParticle, pitch: &SpeakerPitch, index: usize, tracer: &mut Tracer) {
+    let start = phrase.moras.len().saturating_sub(len);
+    let count = phrase.moras.len() - start;
+    for (k, mora) in phrase.moras[start..].iter_mut().enumerate() {
+        // a single mora goes straight to the end pitch
+        let t = if count > 1 { k as f64 / (count - 1) as f64 } else { 1.0 };
+        let factor = particle.pitch_start + (particle.pitch_end - particle.pitch_start) * t;
+        mora.pitch = pitch.scale(mora.pitch, factor);
+        mora.vowel_length *= particle.length;
+        tracer.fire(index, start + k, "final_particle");
+    }
+}
+
+/// How quoted parts of a sentence (「行くの？」と聞いた) are rendered.
+///
+/// A quote is synthesized on its own, so declination starts again from the
+/// top inside it.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuoteStyle {
+    /// Silence before and after the quote, in seconds
+    pub pause: f64,
+
+    /// Pitch factor of the voiced moras of the quote
+    pub pitch: f64,
+}
+
+impl Default for QuoteStyle {
+    fn default() -> Self {
+        Self {
+            pause: 0.15,
+            pitch: 1.02,
+        }
+    }
+}
+
+/// Applies the pitch factor of a quote style to a whole query.
+pub(crate) fn apply_quote_style(query: &mut types::AudioQuery, style: &QuoteStyle, speaker: &SpeakerPitch) {
+    for mora in query.accent_phrases.iter_mut().flat_map(|phrase| phrase.moras.iter_mut()) {
+        if mora.pitch > 0.0 {
+            mora.pitch = speaker.scale(mora.pitch, style.pitch);
+        }
+    }
+}
+
+/// Bounded, smoothly varying jitter on pitch and lengths, so repeated
+/// patterns do not sound mechanical.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HumanizeParams {
+    /// Strength of the jitter; 1.0 varies pitch by up to 2%, vowel length
+    /// by up to 8% and pause length by up to 15%
+    pub amount: f64,
+
+    /// Seed of the jitter; the same seed and text give the same output
+    pub seed: u64,
+
+    /// How much each mora follows the previous one, from 0.0 (independent)
+    /// to below 1.0 (slow drift)
+    pub smoothness: f64,
+}
+
+impl Default for HumanizeParams {
+    fn default() -> Self {
+        Self {
+            amount: 1.0,
+            seed: 0,
+            smoothness: 0.7,
+        }
+    }
+}
+
+impl HumanizeParams {
+    const PITCH_RANGE: f64 = 0.02;
+    const VOWEL_LENGTH_RANGE: f64 = 0.08;
+    const PAUSE_LENGTH_RANGE: f64 = 0.15;
+
+    /// Upper bound of `amount`, keeping every length positive
+    pub const AMOUNT_MAX: f64 = 5.0;
+}
+
+/// A random walk in `-1.0..=1.0`: each step moves towards a fresh uniform
+/// sample by `1 - smoothness`, so it never leaves the range.
+struct Drift {
+    value: f64,
+    smoothness: f64,
+}
+
+impl Drift {
+    fn next(&mut self, rng: &mut SplitMix64) -> f64 {
+        let sample = rng.next_f64() * 2.0 - 1.0;
+        self.value = self.smoothness * self.value + (1.0 - self.smoothness) * sample;
+        self.value
+    }
+}
+
+/// Applies humanization jitter to the transformed phrases.
+///
+/// The sequence is seeded from `params.seed` and the readings, so each
+/// sentence gets its own jitter while staying reproducible.
+pub(crate) fn humanize(accent_phrases: &mut [types::AccentPhrase], params: &HumanizeParams, speaker: &SpeakerPitch, tracer: &mut Tracer) {
+    // FNV-1a over the readings
+    let text_hash = accent_phrases.iter()
+        .flat_map(|phrase| phrase.moras.iter())
+        .flat_map(|mora| mora.text.bytes())
+        .fold(0xCBF2_9CE4_8422_2325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3));
+    let mut rng = SplitMix64::new(params.seed ^ text_hash);
+
+    let amount = if params.amount.is_finite() { params.amount.clamp(0.0, HumanizeParams::AMOUNT_MAX) } else { 0.0 };
+    let smoothness = if params.smoothness.is_finite() { params.smoothness.clamp(0.0, 0.99) } else { 0.0 };
+    let mut pitch = Drift { value: 0.0, smoothness };
+    let mut length = Drift { value: 0.0, smoothness };
+    let mut pause = Drift { value: 0.0, smoothness };
+
+    for (i, phrase) in accent_phrases.iter_mut().enumerate() {
+        for (j, mora) in phrase.moras.iter_mut().enumerate() {
+            let pitch_jitter = pitch.next(&mut rng);
+            let length_jitter = length.next(&mut rng);
+            // devoiced moras have no pitch to move
+            if mora.pitch > 0.0 {
+                mora.pitch = speaker.scale(mora.pitch, 1.0 + amount * HumanizeParams::PITCH_RANGE * pitch_jitter);
+            }
+            mora.vowel_length *= 1.0 + amount * HumanizeParams::VOWEL_LENGTH_RANGE * length_jitter;
+            tracer.fire(i, j, "humanize");
+        }
+        if let Some(pause_mora) = &mut phrase.pause_mora {
+            pause_mora.vowel_length *= 1.0 + amount * HumanizeParams::PAUSE_LENGTH_RANGE * pause.next(&mut rng);
+        }
+    }
+}
+
+/// Drops accent phrases without moras, moving their pause to the previous phrase.
+pub(crate) fn remove_empty_phrases(accent_phrases: &mut Vec<types::AccentPhrase>, tracer: &mut Tracer) {
+    let mut kept: Vec<types::AccentPhrase> = Vec::with_capacity(accent_phrases.len());
+    for phrase in accent_phrases.drain(..) {
+        if !phrase.moras.is_empty() {
+            kept.push(phrase);
+            continue;
+        }
+        tracer.remove_phrase(kept.len());
+        if let Some(prev) = kept.last_mut() {
+            if prev.pause_mora.is_none() {
+                prev.pause_mora = phrase.pause_mora;
+            }
+        }
+    }
+    *accent_phrases = kept;
+}
+
+/// Returns the average voiced pitch of each accent phrase.
+///
+/// Fully devoiced phrases (e.g. 「す。」) have no voiced pitch; they get the
+/// mean of the nearest voiced phrases on either side, or the speaker
+/// baseline if the whole utterance is devoiced.
+pub(crate) fn phrase_pitch_levels(accent_phrases: &[types::AccentPhrase], speaker: &SpeakerPitch) -> Vec<f64> {
+    let averages = accent_phrases.iter().map(|phrase| {
+        let pitches = phrase.moras.iter()
+            .map(|mora| mora.pitch)
+            .filter(|pitch| *pitch > 0.0 && pitch.is_finite())
+            .collect::<Vec<f64>>();
+        (!pitches.is_empty()).then(|| pitches.iter().sum::<f64>() / pitches.len() as f64)
+    }).collect::<Vec<Option<f64>>>();
+
+    (0..averages.len()).map(|i| {
+        if let Some(level) = averages[i] {
+            return level;
+        }
+        let prev = averages[..i].iter().rev().find_map(|level| *level);
+        let next = averages[i + 1..].iter().find_map(|level| *level);
+        match (prev, next) {
+            (Some(prev), Some(next)) => (prev + next) / 2.0,
+            (Some(level), None) | (None, Some(level)) => level,
+            (None, None) => speaker.mean,
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mora(text: &str, consonant: Option<&str>, vowel: &str) -> types::Mora {
+        types::Mora {
+            text: text.to_string(),
+            vowel: vowel.parse().unwrap(),
+            vowel_length: 0.1,
+            pitch: 5.5,
+            consonant: consonant.map(|c| c.parse().unwrap()),
+            consonant_length: consonant.map(|_| 0.05),
+        }
+    }
+
+    fn phrase(moras: Vec<types::Mora>, accent: i32) -> types::AccentPhrase {
+        types::AccentPhrase {
+            moras,
+            accent,
+            pause_mora: None,
+            is_interrogative: false,
+        }
+    }
+
+    /// 秋, 雨: two light syllables
+    fn aki(accent: i32) -> types::AccentPhrase {
+        phrase(vec![mora("ア", None, "a"), mora("キ", Some("k"), "i")], accent)
+    }
+
+    /// 心 (こころ): three light syllables
+    fn kokoro(accent: i32) -> types::AccentPhrase {
+        phrase(vec![mora("コ", Some("k"), "o"), mora("コ", Some("k"), "o"), mora("ロ", Some("r"), "o")], accent)
+    }
+
+    fn prenasalized(phrase: &types::AccentPhrase) -> types::AccentPhrase {
+        let mut result = phrase.clone();
+        apply_prenasalization(&mut result, phrase, &PrenasalizationParams::default(), 0, &mut Tracer::disabled());
+        result
+    }
+
+    fn texts(phrase: &types::AccentPhrase) -> String {
+        phrase.moras.iter().map(|mora| mora.text.as_str()).collect()
+    }
+
+    #[test]
+    fn prenasalization_keeps_accent_on_the_same_mora() {
+        // 窓口 (マドグチ): the nasal before ド lands after, at and before the nucleus
+        let madoguchi = |accent| phrase(vec![
+            mora("マ", Some("m"), "a"),
+            mora("ド", Some("d"), "o"),
+            mora("グ", Some("g"), "u"),
+            mora("チ", Some("ch"), "i"),
+        ], accent);
+
+        let after = prenasalized(&madoguchi(1));
+        assert_eq!(texts(&after), "マンドングチ");
+        assert_eq!(after.accent, 1);
+
+        let at = prenasalized(&madoguchi(2));
+        assert_eq!(at.accent, 3);
+        assert_eq!(at.moras[2].text, "ド");
+
+        let before = prenasalized(&madoguchi(4));
+        assert_eq!(before.accent, 6);
+        assert_eq!(before.moras[5].text, "チ");
+    }
+
+    #[test]
+    fn prenasalization_needs_a_voiced_vowel_before() {
+        // 神田, ベッド, and a devoiced vowel before ド
+        let kanda = phrase(vec![mora("カ", Some("k"), "a"), mora("ン", None, "N"), mora("ダ", Some("d"), "a")], 1);
+        let beddo = phrase(vec![mora("ベ", Some("b"), "e"), mora("ッ", None, "cl"), mora("ド", Some("d"), "o")], 1);
+        let devoiced = phrase(vec![mora("キ", Some("k"), "I"), mora("ド", Some("d"), "o")], 2);
+        for phrase in [kanda, beddo, devoiced] {
+            let result = prenasalized(&phrase);
+            assert_eq!((texts(&result), result.accent), (texts(&phrase), phrase.accent));
+        }
+    }
+
+    fn coalesced(phrase: &types::AccentPhrase) -> types::AccentPhrase {
+        let mut result = phrase.clone();
+        apply_vowel_coalescence(&mut result, &VowelCoalescenceParams::default(), 0, &mut Tracer::disabled());
+        result
+    }
+
+    #[test]
+    fn vowel_coalescence_moves_accent() {
+        // 高いね (タカイネ) → タケネ
+        let takaine = |accent| phrase(vec![
+            mora("タ", Some("t"), "a"),
+            mora("カ", Some("k"), "a"),
+            mora("イ", None, "i"),
+            mora("ネ", Some("n"), "e"),
+        ], accent);
+
+        let before = coalesced(&takaine(1));
+        assert_eq!(texts(&before), "タカネ");
+        assert_eq!(before.moras[1].vowel, Vowel::E);
+        assert!((before.moras[1].vowel_length - 0.2).abs() < 1e-9);
+        assert_eq!(before.accent, 1);
+
+        // the nucleus on the removed mora moves to the fused one
+        assert_eq!(coalesced(&takaine(3)).accent, 2);
+        assert_eq!(coalesced(&takaine(4)).accent, 3);
+    }
+
+    #[test]
+    fn vowel_coalescence_skips_ya_i() {
+        // 早い (ハヤイ): no /ye/ mora to fuse into
+        let hayai = phrase(vec![mora("ハ", Some("h"), "a"), mora("ヤ", Some("y"), "a"), mora("イ", None, "i")], 2);
+        let result = coalesced(&hayai);
+        assert_eq!((texts(&result), result.accent), ("ハヤイ".to_string(), 2));
+    }
+
+    #[test]
+    fn shifted_type_1_peaks_on_second_mora() {
+        assert_eq!(northern_accent_peak(&aki(1), NorthernAccent::Shifted), 1..2);
+        assert_eq!(northern_accent_peak(&kokoro(1), NorthernAccent::Shifted), 1..2);
+    }
+
+    #[test]
+    fn shifted_medial_accent_moves_one_mora_later() {
+        assert_eq!(northern_accent_peak(&kokoro(2), NorthernAccent::Shifted), 2..3);
+    }
+
+    #[test]
+    fn shifted_unaccented_peaks_on_last_syllable() {
+        assert_eq!(northern_accent_peak(&aki(2), NorthernAccent::Shifted), 1..2);
+        assert_eq!(northern_accent_peak(&kokoro(3), NorthernAccent::Shifted), 2..3);
+    }
+
+    #[test]
+    fn shifted_skips_special_moras() {
+        // コーヒー as VOICEVOX spells it (コオヒイ), Tokyo type 3: the nucleus ヒ
+        // is in the final heavy syllable, so the whole of ヒイ carries the peak
+        let coffee = phrase(vec![
+            mora("コ", Some("k"), "o"),
+            mora("オ", None, "o"),
+            mora("ヒ", Some("h"), "i"),
+            mora("イ", None, "i"),
+        ], 3);
+        assert_eq!(northern_accent_peak(&coffee, NorthernAccent::Shifted), 2..4);
+
+        // 東京 (トオキョオ), type 0: the peak does not start on the second half of キョオ
+        let tokyo = phrase(vec![
+            mora("ト", Some("t"), "o"),
+            mora("オ", None, "o"),
+            mora("キョ", Some("ky"), "o"),
+            mora("オ", None, "o"),
+        ], 4);
+        assert_eq!(northern_accent_peak(&tokyo, NorthernAccent::Shifted), 2..4);
+
+        // 通る (トオル), type 1: the peak skips the long vowel to ル
+        let tooru = phrase(vec![mora("ト", Some("t"), "o"), mora("オ", None, "o"), mora("ル", Some("r"), "u")], 1);
+        assert_eq!(northern_accent_peak(&tooru, NorthernAccent::Shifted), 2..3);
+
+        // 青 (アオ): different vowels make two syllables
+        let ao = phrase(vec![mora("ア", None, "a"), mora("オ", None, "o")], 1);
+        assert_eq!(northern_accent_peak(&ao, NorthernAccent::Shifted), 1..2);
+
+        // 新聞 (シンブン), type 0: last syllable ブン
+        let shinbun = phrase(vec![
+            mora("シ", Some("sh"), "i"),
+            mora("ン", None, "N"),
+            mora("ブ", Some("b"), "u"),
+            mora("ン", None, "N"),
+        ], 4);
+        assert_eq!(northern_accent_peak(&shinbun, NorthernAccent::Shifted), 2..4);
+
+        // キッテ read as type 1: the heavy syllable キッ is skipped as a whole
+        let kitte = phrase(vec![
+            mora("キ", Some("k"), "i"),
+            mora("ッ", None, "cl"),
+            mora("テ", Some("t"), "e"),
+        ], 1);
+        assert_eq!(northern_accent_peak(&kitte, NorthernAccent::Shifted), 2..3);
+    }
+
+    #[test]
+    fn legacy_peaks_on_tokyo_nucleus() {
+        assert_eq!(northern_accent_peak(&aki(1), NorthernAccent::Legacy), 0..1);
+        assert_eq!(northern_accent_peak(&kokoro(2), NorthernAccent::Legacy), 1..2);
+        assert_eq!(northern_accent_peak(&kokoro(3), NorthernAccent::Legacy), 2..3);
+    }
+
+    #[test]
+    fn fixed_contour_matches_original_shape() {
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(SouthernContour::default().factors(4, &mut rng), vec![0.96, 1.03, 1.03, 1.04]);
+        assert_eq!(SouthernContour::default().factors(1, &mut rng), vec![0.96]);
+    }
+
+    #[test]
+    fn random_contour_is_reproducible() {
+        let contour = SouthernContour::random(42);
+        let run = || {
+            let mut rng = contour.rng();
+            (0..8).map(|_| contour.factors(5, &mut rng)).collect::<Vec<_>>()
+        };
+        let first = run();
+        assert_eq!(first, run());
+        for factors in first {
+            let peak = factors.iter().position(|f| *f == SouthernShape::default().peak).unwrap();
+            assert!((2..=4).contains(&peak));
+        }
+    }
+
+    #[test]
+    fn declination_resets_after_pause() {
+        let mut phrases = vec![aki(1), kokoro(3), kokoro(3), aki(2)];
+        phrases[1].pause_mora = Some(mora("、", None, "pau"));
+        let params = DeclinationParams {
+            slope: 0.1,
+            reset_on_pause: true,
+            downstep: Some(0.5),
+        };
+        let factors = declination_factors(&phrases, &params);
+        // aki(1) is accented, so the second phrase is downstepped; the pause resets both
+        assert_eq!(factors, vec![1.0, 0.9 * 0.5, 1.0, 0.9]);
+    }
+
+    #[test]
+    fn calibrated_scale_gives_equal_intervals() {
+        let low = SpeakerPitch { mean: 5.0 };
+        let high = SpeakerPitch { mean: 6.0 };
+        let low_delta = low.scale(5.0, 1.07) - 5.0;
+        let high_delta = high.scale(6.0, 1.07) - 6.0;
+        assert!((low_delta - high_delta).abs() < 1e-9);
+        assert_eq!(SpeakerPitch::reference().scale(5.5, 1.07), 5.5 * 1.07);
+    }
+
+    #[test]
+    fn devoiced_phrases_borrow_neighbouring_pitch() {
+        let mut devoiced = kokoro(1);
+        for mora in devoiced.moras.iter_mut() {
+            mora.pitch = 0.0;
+        }
+        let mut high = aki(1);
+        for mora in high.moras.iter_mut() {
+            mora.pitch = 6.5;
+        }
+        let speaker = SpeakerPitch::reference();
+        assert_eq!(phrase_pitch_levels(&[aki(1), devoiced.clone(), high], &speaker), vec![5.5, 6.0, 6.5]);
+        assert_eq!(phrase_pitch_levels(&[devoiced], &speaker), vec![SpeakerPitch::REFERENCE_MEAN]);
+    }
+
+    /// A phrase with one mora per character; only the text and vowels matter for classification
+    fn reading(text: &str, is_interrogative: bool) -> types::AccentPhrase {
+        const ROWS: [(&str, &str); 7] = [
+            ("a", "アカガサザタダナハバパマヤラワ"),
+            ("i", "イキギシジチヂニヒビピミリ"),
+            ("u", "ウクグスズツヅヌフブプムユル"),
+            ("e", "エケゲセゼテデネヘベペメレ"),
+            ("o", "オコゴソゾトドノホボポモヨロヲ"),
+            ("N", "ン"),
+            ("cl", "ッ"),
+        ];
+        let mut moras: Vec<types::Mora> = Vec::new();
+        for c in text.chars() {
+            match c {
+                'ャ' | 'ュ' | 'ョ' => {
+                    let last = moras.last_mut().unwrap();
+                    last.text.push(c);
+                    last.vowel = match c { 'ャ' => Vowel::A, 'ュ' => Vowel::U, _ => Vowel::O };
+                }
+                'ー' => {
+                    let vowel = moras.last().unwrap().vowel;
+                    moras.push(types::Mora { vowel, ..mora("ー", None, "a") });
+                }
+                _ => {
+                    let (vowel, _) = ROWS.iter().find(|(_, row)| row.contains(c)).unwrap();
+                    let consonant = (!"アイウエオヲンッ".contains(c)).then_some("k");
+                    moras.push(mora(&c.to_string(), consonant, vowel));
+                }
+            }
+        }
+        let accent = moras.len() as i32;
+        types::AccentPhrase {
+            is_interrogative,
+            ..phrase(moras, accent)
+        }
+    }
+
+    #[test]
+    fn classifies_questions() {
+        assert_eq!(classify_question(&[reading("イク", true)]), Some(QuestionType::YesNo));
+        assert_eq!(classify_question(&[reading("イク", false)]), None);
+        assert_eq!(classify_question(&[reading("ドコサ", false), reading("イグノ", true)]), Some(QuestionType::Wh));
+        assert_eq!(classify_question(&[reading("ナニ", false), reading("デスカ", false)]), Some(QuestionType::Wh));
+        assert_eq!(classify_question(&[reading("イキマスカ", false)]), Some(QuestionType::Particle));
+        assert_eq!(classify_question(&[reading("ソオダベ", true)]), Some(QuestionType::Particle));
+        assert_eq!(classify_question(&[reading("イッタッケエ", true)]), Some(QuestionType::Particle));
+        assert_eq!(classify_question(&[reading("ナニモ", false), reading("ナイノ", true)]), Some(QuestionType::YesNo));
+        assert_eq!(classify_question(&[reading("ナニカ", false), reading("アッタ", true)]), Some(QuestionType::YesNo));
+        assert_eq!(classify_question(&[reading("ドオシテ", false), reading("イグノ", true)]), Some(QuestionType::Wh));
+        assert_eq!(classify_question(&[reading("ドオヤッテ", false), reading("イグノ", true)]), Some(QuestionType::Wh));
+        assert_eq!(classify_question(&[reading("ドコカラ", false), reading("キタノ", true)]), Some(QuestionType::Wh));
+        assert_eq!(classify_question(&[reading("ナンジニ", false), reading("クルノ", true)]), Some(QuestionType::Wh));
+    }
+
+    #[test]
+    fn question_words_need_a_word_boundary() {
+        assert_eq!(classify_question(&[reading("ナンブニ", false), reading("イグノ", true)]), Some(QuestionType::YesNo));
+        assert_eq!(classify_question(&[reading("ナンキョクワ", false), reading("サムイノ", true)]), Some(QuestionType::YesNo));
+        assert_eq!(classify_question(&[reading("ドレスヲ", false), reading("カウノ", true)]), Some(QuestionType::YesNo));
+        assert_eq!(classify_question(&[reading("イツツ", false), reading("アルノ", true)]), Some(QuestionType::YesNo));
+    }
+
+    #[test]
+    fn question_types_follow_intonation_units() {
+        let mut first = reading("イグベ", true);
+        first.pause_mora = Some(types::Mora {
+            vowel_length: 0.4,
+            ..mora("、", None, "pau")
+        });
+        let phrases = [first, reading("ドコ", false), reading("イグノ", true)];
+        assert_eq!(question_types(&phrases, true), vec![Some(QuestionType::Particle), None, Some(QuestionType::Wh)]);
+        assert_eq!(question_types(&phrases, false), vec![Some(QuestionType::YesNo), None, Some(QuestionType::YesNo)]);
+    }
+
+    #[test]
+    fn final_particles_match_longest_reading() {
+        let table = FinalParticle::defaults();
+        let phrases = [reading("ンダナヤアア", false)];
+        let found = final_particles(&phrases, &table, &[None], Some("んだなやー"));
+        let (len, particle) = found[0].unwrap();
+        assert_eq!((len, particle.reading.as_str()), (4, "ナヤ"));
+
+        let phrases = [reading("ソオダベ", true)];
+        let found = final_particles(&phrases, &table, &[Some(QuestionType::Particle)], Some("そうだべ？"));
+        let (len, particle) = found[0].unwrap();
+        assert_eq!((len, particle.reading.as_str(), particle.question), (2, "ダベ", Some(true)));
+
+        let phrases = [reading("イク", false)];
+        assert_eq!(final_particles(&phrases, &table, &[None], Some("行く")), vec![None]);
+    }
+
+    #[test]
+    fn single_mora_particles_must_be_written() {
+        let table = FinalParticle::defaults();
+        let phrases = [reading("サカナ", false)];
+        assert_eq!(final_particles(&phrases, &table, &[None], Some("魚。")), vec![None]);
+        assert_eq!(final_particles(&phrases, &table, &[None], None), vec![None]);
+
+        let phrases = [reading("ソオダネエ", false)];
+        let found = final_particles(&phrases, &table, &[None], Some("そうだねえ！"));
+        let (len, particle) = found[0].unwrap();
+        assert_eq!((len, particle.reading.as_str()), (2, "ネ"));
+        assert_eq!(final_particles(&phrases, &table, &[None], None), vec![None]);
+
+        // clauses line up with intonation units
+        let mut first = reading("ハナ", false);
+        first.pause_mora = Some(types::Mora {
+            vowel_length: 0.4,
+            ..mora("、", None, "pau")
+        });
+        let phrases = [first, reading("キレイダナ", false)];
+        let found = final_particles(&phrases, &table, &[None, None], Some("花、きれいだな"));
+        assert_eq!(found[0], None);
+        assert_eq!(found[1].map(|(len, particle)| (len, particle.reading.as_str())), Some((1, "ナ")));
+    }
+
+    #[test]
+    fn final_particle_interpolates_pitch() {
+        let mut phrase = reading("ナヤ", false);
+        let particle = FinalParticle::new("ナヤ", None, 1.0, 0.9, 2.0);
+        apply_final_particle(&mut phrase, 2, &particle, &SpeakerPitch::reference(), 0, &mut Tracer::disabled());
+        assert_eq!(phrase.moras[0].pitch, 5.5);
+        assert!((phrase.moras[1].pitch - 5.5 * 0.9).abs() < 1e-9);
+        assert_eq!(phrase.moras[1].vowel_length, 0.2);
+    }
+
+    #[test]
+    fn humanize_is_reproducible_and_bounded() {
+        let phrases = vec![kokoro(2), aki(1), kokoro(3)];
+        let params = HumanizeParams { amount: 1.0, seed: 42, smoothness: 0.7 };
+        let run = |params: &HumanizeParams| {
+            let mut phrases = phrases.clone();
+            humanize(&mut phrases, params, &SpeakerPitch::reference(), &mut Tracer::disabled());
+            phrases.iter().flat_map(|p| p.moras.iter().map(|m| (m.pitch, m.vowel_length))).collect::<Vec<_>>()
+        };
+        let a = run(&params);
+        assert_eq!(a, run(&params));
+        assert_ne!(a, run(&HumanizeParams { seed: 43, ..params }));
+        for (pitch, length) in a {
+            assert!((pitch / 5.5 - 1.0).abs() <= 0.02 + 1e-9);
+            assert!((length / 0.1 - 1.0).abs() <= 0.08 + 1e-9);
+        }
+    }
+}
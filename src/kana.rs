@@ -0,0 +1,102 @@
+use crate::types::{self, Consonant, Vowel};
+
+const ACCENT_SYMBOL: char = '\'';
+const UNVOICE_SYMBOL: char = '_';
+const NOPAUSE_DELIMITER: char = '/';
+const PAUSE_DELIMITER: char = '、';
+const WIDE_INTERROGATION_MARK: char = '？';
+
+/// Builds the AquesTalk-style kana notation for the given accent phrases,
+/// in the same form as `AudioQuery.kana` returned by VOICEVOX Core.
+pub fn create_kana(accent_phrases: &[types::AccentPhrase]) -> String {
+    let mut text = String::new();
+    for phrase in accent_phrases {
+        for (index, mora) in phrase.moras.iter().enumerate() {
+            if mora.vowel.is_devoiced() {
+                text.push(UNVOICE_SYMBOL);
+            }
+            text.push_str(&mora.text);
+            if index as i32 + 1 == phrase.accent {
+                text.push(ACCENT_SYMBOL);
+            }
+        }
+        if phrase.is_interrogative {
+            text.push(WIDE_INTERROGATION_MARK);
+        }
+        text.push(if phrase.pause_mora.is_some() { PAUSE_DELIMITER } else { NOPAUSE_DELIMITER });
+    }
+    text.pop(); // trailing delimiter
+    text
+}
+
+/// Regenerates `Mora.text` from the phonemes of every mora and rebuilds
+/// `AudioQuery.kana`, so both describe what is actually sent to synthesis.
+///
+/// Moras whose phonemes have no katakana keep their original text.
+pub fn update_query(query: &mut types::AudioQuery) {
+    for phrase in query.accent_phrases.iter_mut() {
+        for mora in phrase.moras.iter_mut() {
+            // keep long vowel marks; a bare vowel after the same vowel reads the same
+            if mora.text == "ー" && mora.consonant.is_none() {
+                continue;
+            }
+            if let Some(text) = mora_to_text(mora.consonant, mora.vowel) {
+                mora.text = text.to_string();
+            }
+        }
+    }
+    query.kana = Some(create_kana(&query.accent_phrases));
+}
+
+/// Returns the katakana for a consonant/vowel pair in Open JTalk notation.
+///
+/// Devoiced (upper-case) vowels map to the same katakana as their voiced forms.
+pub fn mora_to_text(consonant: Option<Consonant>, vowel: Vowel) -> Option<&'static str> {
+    let index = match vowel.voiced() {
+        Vowel::A => 0,
+        Vowel::I => 1,
+        Vowel::U => 2,
+        Vowel::E => 3,
+        Vowel::O => 4,
+        Vowel::N => return consonant.is_none().then_some("ン"),
+        Vowel::Cl => return consonant.is_none().then_some("ッ"),
+        Vowel::Pau => return consonant.is_none().then_some("、"),
+        _ => return None,
+    };
+    let row: [&'static str; 5] = match consonant {
+        None => ["ア", "イ", "ウ", "エ", "オ"],
+        Some(Consonant::K) => ["カ", "キ", "ク", "ケ", "コ"],
+        Some(Consonant::Ky) => ["キャ", "", "キュ", "キェ", "キョ"],
+        Some(Consonant::G) => ["ガ", "ギ", "グ", "ゲ", "ゴ"],
+        Some(Consonant::Gy) => ["ギャ", "", "ギュ", "ギェ", "ギョ"],
+        Some(Consonant::S) => ["サ", "スィ", "ス", "セ", "ソ"],
+        Some(Consonant::Sh) => ["シャ", "シ", "シュ", "シェ", "ショ"],
+        Some(Consonant::Z) => ["ザ", "ズィ", "ズ", "ゼ", "ゾ"],
+        Some(Consonant::J) => ["ジャ", "ジ", "ジュ", "ジェ", "ジョ"],
+        Some(Consonant::T) => ["タ", "ティ", "トゥ", "テ", "ト"],
+        Some(Consonant::Ty) => ["テャ", "", "テュ", "", "テョ"],
+        Some(Consonant::Ch) => ["チャ", "チ", "チュ", "チェ", "チョ"],
+        Some(Consonant::Ts) => ["ツァ", "ツィ", "ツ", "ツェ", "ツォ"],
+        Some(Consonant::D) => ["ダ", "ディ", "ドゥ", "デ", "ド"],
+        Some(Consonant::Dy) => ["デャ", "", "デュ", "", "デョ"],
+        Some(Consonant::N) => ["ナ", "ニ", "ヌ", "ネ", "ノ"],
+        Some(Consonant::Ny) => ["ニャ", "", "ニュ", "ニェ", "ニョ"],
+        Some(Consonant::H) => ["ハ", "ヒ", "ホゥ", "ヘ", "ホ"],
+        Some(Consonant::Hy) => ["ヒャ", "", "ヒュ", "ヒェ", "ヒョ"],
+        Some(Consonant::F) => ["ファ", "フィ", "フ", "フェ", "フォ"],
+        Some(Consonant::B) => ["バ", "ビ", "ブ", "ベ", "ボ"],
+        Some(Consonant::By) => ["ビャ", "", "ビュ", "ビェ", "ビョ"],
+        Some(Consonant::P) => ["パ", "ピ", "プ", "ペ", "ポ"],
+        Some(Consonant::Py) => ["ピャ", "", "ピュ", "ピェ", "ピョ"],
+        Some(Consonant::M) => ["マ", "ミ", "ム", "メ", "モ"],
+        Some(Consonant::My) => ["ミャ", "", "ミュ", "ミェ", "ミョ"],
+        Some(Consonant::Y) => ["ヤ", "", "ユ", "イェ", "ヨ"],
+        Some(Consonant::R) => ["ラ", "リ", "ル", "レ", "ロ"],
+        Some(Consonant::Ry) => ["リャ", "", "リュ", "リェ", "リョ"],
+        Some(Consonant::W) => ["ワ", "ウィ", "", "ウェ", "ウォ"],
+        Some(Consonant::V) => ["ヴァ", "ヴィ", "ヴ", "ヴェ", "ヴォ"],
+        Some(Consonant::Kw) => ["クヮ", "", "", "", ""],
+        Some(Consonant::Gw) => ["グヮ", "", "", "", ""],
+    };
+    Some(row[index]).filter(|text| !text.is_empty())
+}
@@ -0,0 +1,222 @@
+//! Optional rewriting of standard Japanese text into Tohoku-flavoured forms.
+//!
+//! Like the rest of the crate, this aims at the generic, outward-facing
+//! Tohoku common speech (他所行きの発話), not at any traditional dialect:
+//! the tables only cover a few frequent sentence-final forms, the copula,
+//! volitional forms and common adverbs.
+
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Reverse;
+
+use crate::SynthesisVariant;
+
+/// Where a rule may match.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RulePosition {
+    #[default]
+    Anywhere,
+
+    /// Only before the end of a sentence: end of text, punctuation or a closing bracket
+    Final,
+}
+
+/// Required character right before the match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preceding {
+    /// A kanji, i.e. the stem of a verb or adjective
+    Kanji,
+
+    /// Anything but hiragana, i.e. the start of a word
+    WordStart,
+
+    /// Start of the text, whitespace, punctuation or an opening bracket,
+    /// i.e. the start of a sentence or clause
+    ClauseStart,
+
+    /// One of the given characters
+    Chars(String),
+}
+
+impl Preceding {
+    fn accepts(&self, c: Option<char>) -> bool {
+        match self {
+            Self::Kanji => c.is_some_and(is_kanji),
+            Self::WordStart => !c.is_some_and(is_hiragana),
+            Self::ClauseStart => c.is_none_or(|c| c.is_whitespace() || "。、！？!?…「『（(".contains(c)),
+            Self::Chars(chars) => c.is_some_and(|c| chars.contains(c)),
+        }
+    }
+}
+
+/// One substitution of the conversion dictionary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LexicalRule {
+    pub from: String,
+    pub to: String,
+
+    #[serde(default)]
+    pub position: RulePosition,
+
+    /// `None` matches after any character
+    #[serde(default)]
+    pub preceding: Option<Preceding>,
+}
+
+impl LexicalRule {
+    fn new(from: &str, to: &str, position: RulePosition, preceding: Option<Preceding>) -> Self {
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            position,
+            preceding,
+        }
+    }
+
+    fn matches(&self, chars: &[char], start: usize) -> Option<usize> {
+        let len = self.from.chars().count();
+        if len == 0 || start + len > chars.len() {
+            return None;
+        }
+        if !chars[start..start + len].iter().copied().eq(self.from.chars()) {
+            return None;
+        }
+        if let Some(preceding) = &self.preceding {
+            if !preceding.accepts(start.checked_sub(1).map(|i| chars[i])) {
+                return None;
+            }
+        }
+        if self.position == RulePosition::Final && !chars.get(start + len).is_none_or(|c| is_sentence_boundary(*c)) {
+            return None;
+        }
+        Some(len)
+    }
+}
+
+pub(crate) fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '々')
+}
+
+fn is_hiragana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{309F}')
+}
+
+fn is_sentence_boundary(c: char) -> bool {
+    c.is_whitespace() || "。、！？!?…」』）)".contains(c)
+}
+
+/// Rewrites `text` with the given rules.
+///
+/// Text is scanned from the start; at each position the longest matching
+/// rule wins, and replaced text is not matched again.
+pub fn convert(text: &str, rules: &[LexicalRule]) -> String {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut rules = rules.iter().collect::<Vec<_>>();
+    rules.sort_by_key(|rule| Reverse(rule.from.chars().count()));
+
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match rules.iter().find_map(|rule| rule.matches(&chars, i).map(|len| (rule, len))) {
+            Some((rule, len)) => {
+                result.push_str(&rule.to);
+                i += len;
+            },
+            None => {
+                result.push(chars[i]);
+                i += 1;
+            },
+        }
+    }
+    result
+}
+
+/// Rules shared by both variants
+fn common_rules() -> Vec<LexicalRule> {
+    use Preceding::*;
+    use RulePosition::*;
+
+    // not じ: 同じよう is far more common than 信じよう
+    let ichidan_stem = "えけげせぜてでねへべぺめれいきぎしちにひびぴみり";
+    vec![
+        // conjecture
+        LexicalRule::new("だろう", "だべ", Final, None),
+        LexicalRule::new("だろ", "だべ", Final, None),
+        LexicalRule::new("でしょう", "だべ", Final, None),
+
+        // copula
+        LexicalRule::new("そうだ", "んだ", Anywhere, Some(ClauseStart)),
+
+        // volitional: godan stems, する and ichidan stems
+        LexicalRule::new("こう", "くべ", Final, Some(Kanji)),
+        LexicalRule::new("ごう", "ぐべ", Final, Some(Kanji)),
+        LexicalRule::new("とう", "つべ", Final, Some(Kanji)),
+        LexicalRule::new("のう", "ぬべ", Final, Some(Kanji)),
+        LexicalRule::new("ぼう", "ぶべ", Final, Some(Kanji)),
+        LexicalRule::new("もう", "むべ", Final, Some(Kanji)),
+        LexicalRule::new("ろう", "るべ", Final, Some(Kanji)),
+        LexicalRule::new("おう", "うべ", Final, Some(Kanji)),
+        LexicalRule::new("しよう", "すべ", Final, None),
+        LexicalRule::new("よう", "るべ", Final, Some(Chars(ichidan_stem.to_string()))),
+        LexicalRule::new("よう", "るべ", Final, Some(Kanji)),
+
+        // adverbs
+        LexicalRule::new("とても", "いっぺ", Anywhere, Some(WordStart)),
+        LexicalRule::new("たくさん", "いっぺ", Anywhere, Some(WordStart)),
+    ]
+}
+
+/// Built-in conversion dictionary of a variant.
+pub fn default_rules(variant: SynthesisVariant) -> Vec<LexicalRule> {
+    use RulePosition::*;
+
+    let mut rules = common_rules();
+    match variant {
+        SynthesisVariant::Northern => {
+            rules.push(LexicalRule::new("ですね", "ですな", Final, None));
+            rules.push(LexicalRule::new("だね", "だな", Final, None));
+        },
+        SynthesisVariant::Southern => {
+            rules.push(LexicalRule::new("ですね", "ですなや", Final, None));
+            rules.push(LexicalRule::new("だね", "だなや", Final, None));
+            rules.push(LexicalRule::new("だよ", "だっちゃ", Final, None));
+        },
+    }
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_final_forms() {
+        let rules = default_rules(SynthesisVariant::Northern);
+        assert_eq!(convert("明日は雨だろう。", &rules), "明日は雨だべ。");
+        assert_eq!(convert("一緒に行こう！勉強しよう", &rules), "一緒に行くべ！勉強すべ");
+        assert_eq!(convert("早く食べよう。見よう", &rules), "早く食べるべ。見るべ");
+        assert_eq!(convert("そうだから", &rules), "んだから");
+        assert_eq!(convert("とても寒いですね", &rules), "いっぺ寒いですな");
+    }
+
+    #[test]
+    fn respects_context() {
+        let rules = default_rules(SynthesisVariant::Southern);
+        // not sentence-final
+        assert_eq!(convert("だろうと思う", &rules), "だろうと思う");
+        assert_eq!(convert("行こうか", &rules), "行こうか");
+        // not after a kanji stem
+        assert_eq!(convert("ありがとう。", &rules), "ありがとう。");
+        // not after the adjective 同じ
+        assert_eq!(convert("同じよう。", &rules), "同じよう。");
+        // not at the start of a word
+        assert_eq!(convert("ほそうだ", &rules), "ほそうだ");
+        // not after a stem (様態のそうだ)
+        assert_eq!(convert("元気そうだ", &rules), "元気そうだ");
+        assert_eq!(convert("大丈夫そうだね", &rules), "大丈夫そうだなや");
+        assert_eq!(convert("ああ、そうだ。", &rules), "ああ、んだ。");
+        assert_eq!(convert("元気だよ。", &rules), "元気だっちゃ。");
+    }
+}
@@ -2,6 +2,17 @@
 pub mod error;
 mod vvc;
 pub mod types;
+pub mod dialect;
+pub mod kana;
+pub mod lexical;
+pub mod normalize;
+pub mod user_dict;
+pub mod ssml;
+pub mod script;
+mod audio;
+mod rng;
+pub mod splitter;
+pub mod trace;
 
 pub mod deps {
     pub use serde_json;
@@ -9,6 +20,8 @@ pub mod deps {
 }
 
 pub use vvc::*;
+pub use dialect::DialectProfile;
+pub use splitter::{TextSplitter, TextSplitterBuilder, Sentence, SentencePart, PartKind};
 
 pub use error::{
     ErrorDescription,
@@ -36,29 +49,3 @@ impl ErrorDescription for EngineErrorDescription {
 }
 
 pub type EngineError = GenericError<EngineErrorDescription>;
-
-pub struct TextSplitter {
-    sentence_splitter: Vec<String>,
-}
-
-impl Default for TextSplitter {
-    fn default() -> Self {
-        Self {
-            sentence_splitter: vec!["。".to_string(), "？".to_string(), "！".to_string(), "!".to_string(), "?".to_string(), "\n".to_string()],
-        }
-    }
-}
-
-impl TextSplitter {
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    pub fn split_text(&self, text: &str) -> Vec<String> {
-        let sentences = self.sentence_splitter.iter().fold(vec![text.to_owned()], |acc, splitter| {
-            acc.iter().flat_map(|sentence| sentence.split(splitter)).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<String>>()
-        });
-
-        sentences
-    }
-}
@@ -0,0 +1,471 @@
+//! Rewrites text that Open JTalk tends to misread (numbers, dates, units,
+//! URLs, ruby, …) into plain Japanese before it reaches `audio_query`.
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use std::sync::LazyLock;
+
+/// Categories of normalization, each of which can be switched off.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizeOptions {
+    /// Ruby (｜東北《とうほく》, 漢字《かんじ》), read as annotated, and
+    /// Aozora Bunko notes (［＃改ページ］), dropped
+    pub ruby: bool,
+
+    /// Half-width katakana and full-width letters and digits
+    pub width: bool,
+
+    /// Numbers, including thousands separators, decimals and signs
+    pub numbers: bool,
+
+    /// Dates such as 2026/10/17
+    pub dates: bool,
+
+    /// Times such as 12:30
+    pub times: bool,
+
+    /// Amounts with a currency symbol (¥, $, €, £)
+    pub currency: bool,
+
+    /// Units after a number (km, ℃, %, …)
+    pub units: bool,
+
+    /// Phone numbers such as 090-1234-5678, read digit by digit
+    pub phone_numbers: bool,
+
+    /// URLs and e-mail addresses
+    pub urls: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            ruby: true,
+            width: true,
+            numbers: true,
+            dates: true,
+            times: true,
+            currency: true,
+            units: true,
+            phone_numbers: true,
+            urls: true,
+        }
+    }
+}
+
+impl NormalizeOptions {
+    /// All categories switched off
+    pub fn none() -> Self {
+        Self {
+            ruby: false,
+            width: false,
+            numbers: false,
+            dates: false,
+            times: false,
+            currency: false,
+            units: false,
+            phone_numbers: false,
+            urls: false,
+        }
+    }
+}
+
+const HALF_WIDTH_KATAKANA: &str = "ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝ｡｢｣､･";
+const FULL_WIDTH_KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン。「」、・";
+const VOICEABLE: &str = "カキクケコサシスセソタチツテトハヒフヘホウ";
+const VOICED: &str = "ガギグゲゴザジズゼゾダヂヅデドバビブベボヴ";
+const SEMI_VOICEABLE: &str = "ハヒフヘホ";
+const SEMI_VOICED: &str = "パピプペポ";
+
+/// Converts half-width katakana (with voicing marks) to full width, and
+/// full-width ASCII letters and digits to half width.
+fn normalize_width(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\u{FF9E}' | '\u{FF9F}' => {
+                let (bases, marked, mark) = match c {
+                    '\u{FF9E}' => (VOICEABLE, VOICED, '゛'),
+                    _ => (SEMI_VOICEABLE, SEMI_VOICED, '゜'),
+                };
+                let combined = result.chars().last()
+                    .and_then(|base| bases.chars().position(|b| b == base))
+                    .and_then(|i| marked.chars().nth(i));
+                match combined {
+                    Some(combined) => {
+                        result.pop();
+                        result.push(combined);
+                    },
+                    None => result.push(mark),
+                }
+            },
+            '\u{FF10}'..='\u{FF19}' | '\u{FF21}'..='\u{FF3A}' | '\u{FF41}'..='\u{FF5A}' => {
+                result.push(char::from_u32(c as u32 - 0xFEE0).unwrap());
+            },
+            _ => match HALF_WIDTH_KATAKANA.chars().position(|h| h == c) {
+                Some(i) => result.push(FULL_WIDTH_KATAKANA.chars().nth(i).unwrap()),
+                None => result.push(c),
+            },
+        }
+    }
+    result
+}
+
+const DIGITS: [&str; 10] = ["〇", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+
+/// Reads a number below 10000 in kanji, without a leading 一 on 十, 百 and 千.
+fn kanji_below_10000(n: u64) -> String {
+    let mut result = String::new();
+    for (unit, name) in [(1000, "千"), (100, "百"), (10, "十")] {
+        let digit = (n / unit) % 10;
+        if digit > 1 {
+            result.push_str(DIGITS[digit as usize]);
+        }
+        if digit > 0 {
+            result.push_str(name);
+        }
+    }
+    let ones = (n % 10) as usize;
+    if ones > 0 {
+        result.push_str(DIGITS[ones]);
+    }
+    result
+}
+
+/// Reads a string of ASCII digits as a kanji number (1234 → 千二百三十四).
+///
+/// Numbers beyond the 京 range are read digit by digit.
+pub fn kanji_number(digits: &str) -> String {
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return "ゼロ".to_string();
+    }
+    if digits.len() > 20 {
+        return digits_one_by_one(digits);
+    }
+    let n: u64 = match digits.parse() {
+        Ok(n) => n,
+        Err(_) => return digits_one_by_one(digits),
+    };
+
+    let mut groups = Vec::new();
+    let mut rest = n;
+    for unit in ["", "万", "億", "兆", "京"] {
+        let group = rest % 10000;
+        if group > 0 {
+            groups.push(format!("{}{}", kanji_below_10000(group), unit));
+        }
+        rest /= 10000;
+        if rest == 0 {
+            break;
+        }
+    }
+    groups.reverse();
+    groups.concat()
+}
+
+fn digits_one_by_one(digits: &str) -> String {
+    digits.chars().filter_map(|c| c.to_digit(10)).map(|d| DIGITS[d as usize]).collect()
+}
+
+/// Reads a number with optional sign, separators and decimals.
+pub fn read_number(number: &str) -> String {
+    let (sign, number) = match number.strip_prefix(['-', '−']) {
+        Some(rest) => ("マイナス", rest),
+        None => ("", number.strip_prefix('+').unwrap_or(number)),
+    };
+    let number = number.replace(',', "");
+    match number.split_once('.') {
+        Some((int, frac)) => format!("{}{}点{}", sign, kanji_number(int), digits_one_by_one(frac)),
+        None => format!("{}{}", sign, kanji_number(&number)),
+    }
+}
+
+/// Readings of days of the month that do not follow the numeral + 日 pattern
+fn day_reading(day: u32) -> String {
+    let special = match day {
+        1 => "ついたち",
+        2 => "ふつか",
+        3 => "みっか",
+        4 => "よっか",
+        5 => "いつか",
+        6 => "むいか",
+        7 => "なのか",
+        8 => "ようか",
+        9 => "ここのか",
+        10 => "とおか",
+        14 => "じゅうよっか",
+        20 => "はつか",
+        24 => "にじゅうよっか",
+        _ => return format!("{}日", kanji_number(&day.to_string())),
+    };
+    special.to_string()
+}
+
+const PHONE_DIGITS: [&str; 10] = ["ゼロ", "イチ", "ニー", "サン", "ヨン", "ゴー", "ロク", "ナナ", "ハチ", "キュー"];
+
+/// Units read after a number; the pattern tries the longest first
+const UNITS: &[(&str, &str)] = &[
+    ("km/h", "キロメートル毎時"),
+    ("kHz", "キロヘルツ"), ("MHz", "メガヘルツ"), ("GHz", "ギガヘルツ"), ("Hz", "ヘルツ"),
+    ("km", "キロメートル"), ("cm", "センチメートル"), ("mm", "ミリメートル"), ("m", "メートル"),
+    ("kg", "キログラム"), ("mg", "ミリグラム"), ("g", "グラム"),
+    ("mL", "ミリリットル"), ("ml", "ミリリットル"), ("L", "リットル"),
+    ("KB", "キロバイト"), ("MB", "メガバイト"), ("GB", "ギガバイト"), ("TB", "テラバイト"),
+    ("°C", "度"), ("℃", "度"), ("%", "パーセント"), ("％", "パーセント"),
+];
+
+const NUMBER: &str = r"[-−+]?(?:\d{1,3}(?:,\d{3})+|\d+)(?:\.\d+)?";
+
+static AOZORA_NOTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"※?［＃[^］\n]*］").unwrap());
+static EMPHASIS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"《《([^《》\n]*)》》").unwrap());
+static RUBY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[｜|]([^｜|《》\n]+)《([^《》\n]*)》").unwrap());
+static PAREN_RUBY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[｜|]([^｜|《》()（）\n]+)[(（]([\p{Hiragana}\p{Katakana}ー]+)[)）]").unwrap());
+static IMPLICIT_RUBY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"([\p{Han}々〆ヶ]+|[\p{Katakana}ー]+|[A-Za-z0-9Ａ-Ｚａ-ｚ０-９]+)《([^《》\n]*)》").unwrap()
+});
+static STRAY_RUBY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"《[^《》\n]*》").unwrap());
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"https?://([A-Za-z0-9.-]+)[A-Za-z0-9\-._~:/?#\[\]@!$&'()*+,;=%]*").unwrap());
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"([A-Za-z0-9._%+-]+)@([A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)+)").unwrap());
+static DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?-u:\b)([1-9]\d{3})[/-](\d{1,2})[/-](\d{1,2})(?-u:\b)").unwrap());
+static TIME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?-u:\b)(\d{1,2}):(\d{2})(?::(\d{2}))?(?-u:\b)").unwrap());
+static PHONE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?-u:\b)(0\d{1,4})-(\d{1,4})-(\d{3,4})(?-u:\b)").unwrap());
+static CURRENCY: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!(r"([¥￥$＄€£])\s?({})", NUMBER)).unwrap());
+static NUMBER_WITH_UNIT: LazyLock<Regex> = LazyLock::new(|| {
+    // alternation takes the first unit that matches, so "mg" must come before "m"
+    let mut units = UNITS.iter().map(|(unit, _)| *unit).collect::<Vec<_>>();
+    units.sort_by_key(|unit| std::cmp::Reverse(unit.chars().count()));
+    let units = units.iter().map(|unit| regex::escape(unit)).collect::<Vec<_>>().join("|");
+    Regex::new(&format!(r"(^|[^A-Za-z0-9.,])({})\s?({})?([A-Za-z]?)", NUMBER, units)).unwrap()
+});
+
+/// Replaces ruby with its reading and drops Aozora Bunko notes.
+///
+/// Besides the Aozora Bunko forms, the web-novel forms `｜漢字(かんじ)` and
+/// `《《傍点》》` are accepted; an empty reading keeps the base text.
+fn replace_ruby(text: &str) -> String {
+    let reading = |caps: &Captures| match &caps[2] {
+        "" => caps[1].to_string(),
+        reading => reading.to_string(),
+    };
+    let text = AOZORA_NOTE.replace_all(text, "");
+    let text = EMPHASIS.replace_all(&text, "$1");
+    let text = RUBY.replace_all(&text, reading);
+    let text = PAREN_RUBY.replace_all(&text, reading);
+    let text = IMPLICIT_RUBY.replace_all(&text, reading);
+    // ruby after hiragana has no base we can find; read the text as written
+    STRAY_RUBY.replace_all(&text, "").into_owned()
+}
+
+fn dotted(host: &str) -> String {
+    host.split('.').filter(|part| !part.is_empty()).collect::<Vec<_>>().join(" ドット ")
+}
+
+fn replace_urls(text: &str) -> String {
+    let text = URL.replace_all(text, |caps: &Captures| format!(" {} ", dotted(&caps[1])));
+    EMAIL.replace_all(&text, |caps: &Captures| format!(" {} アットマーク {} ", dotted(&caps[1]), dotted(&caps[2]))).into_owned()
+}
+
+fn replace_dates(text: &str) -> String {
+    DATE.replace_all(text, |caps: &Captures| {
+        let month: u32 = caps[2].parse().unwrap_or(0);
+        let day: u32 = caps[3].parse().unwrap_or(0);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return caps[0].to_string();
+        }
+        format!("{}年{}月{}", kanji_number(&caps[1]), kanji_number(&caps[2]), day_reading(day))
+    }).into_owned()
+}
+
+fn replace_times(text: &str) -> String {
+    TIME.replace_all(text, |caps: &Captures| {
+        let hour: u32 = caps[1].parse().unwrap_or(99);
+        let minute: u32 = caps[2].parse().unwrap_or(99);
+        let second: Option<u32> = caps.get(3).and_then(|s| s.as_str().parse().ok());
+        if hour > 24 || minute > 59 || second.is_some_and(|s| s > 59) {
+            return caps[0].to_string();
+        }
+        let mut result = format!("{}時", kanji_number(&caps[1]));
+        if minute > 0 {
+            result.push_str(&format!("{}分", kanji_number(&caps[2])));
+        }
+        if let Some(second) = second.filter(|s| *s > 0) {
+            result.push_str(&format!("{}秒", kanji_number(&second.to_string())));
+        }
+        result
+    }).into_owned()
+}
+
+const LETTER_NAMES: [&str; 26] = [
+    "エー", "ビー", "シー", "ディー", "イー", "エフ", "ジー", "エイチ", "アイ", "ジェー", "ケー", "エル", "エム",
+    "エヌ", "オー", "ピー", "キュー", "アール", "エス", "ティー", "ユー", "ブイ", "ダブリュー", "エックス", "ワイ", "ゼット",
+];
+
+/// Reads the digits of `text` one by one, as in phone numbers (123 → イチニーサン).
+///
+/// Hyphens between groups are read as の; anything else is dropped.
+pub fn read_digits(text: &str) -> String {
+    text.split(['-', '‐', '−']).map(|group| {
+        group.chars().filter_map(|c| c.to_digit(10)).map(|d| PHONE_DIGITS[d as usize]).collect::<String>()
+    }).filter(|group| !group.is_empty()).collect::<Vec<_>>().join("の")
+}
+
+/// Reads letters and digits one character at a time (AB1 → エー ビー イチ).
+pub fn spell_out(text: &str) -> String {
+    normalize_width(text).chars().filter(|c| !c.is_whitespace()).map(|c| match c {
+        'A'..='Z' => LETTER_NAMES[(c as u8 - b'A') as usize].to_string(),
+        'a'..='z' => LETTER_NAMES[(c as u8 - b'a') as usize].to_string(),
+        '0'..='9' => PHONE_DIGITS[c as usize - '0' as usize].to_string(),
+        _ => c.to_string(),
+    }).collect::<Vec<_>>().join(" ")
+}
+
+fn replace_phone_numbers(text: &str) -> String {
+    PHONE.replace_all(text, |caps: &Captures| read_digits(&caps[0])).into_owned()
+}
+
+fn replace_currency(text: &str) -> String {
+    CURRENCY.replace_all(text, |caps: &Captures| {
+        let unit = match &caps[1] {
+            "¥" | "￥" => "円",
+            "$" | "＄" => "ドル",
+            "€" => "ユーロ",
+            _ => "ポンド",
+        };
+        format!("{}{}", read_number(&caps[2]), unit)
+    }).into_owned()
+}
+
+fn replace_numbers(text: &str, options: &NormalizeOptions) -> String {
+    NUMBER_WITH_UNIT.replace_all(text, |caps: &Captures| {
+        let unit = caps.get(3).map(|unit| unit.as_str());
+        let trailing = &caps[4];
+        // a letter right after the unit means it was part of a word (5min, A4)
+        let unit = unit.filter(|_| trailing.is_empty() && options.units);
+        let number = if options.numbers { read_number(&caps[2]) } else { caps[2].to_string() };
+        match unit {
+            Some(unit) => {
+                let reading = UNITS.iter().find(|(u, _)| *u == unit).map_or(unit, |(_, reading)| reading);
+                format!("{}{}{}", &caps[1], number, reading)
+            },
+            None => format!("{}{}{}{}", &caps[1], number, caps.get(3).map_or("", |u| u.as_str()), trailing),
+        }
+    }).into_owned()
+}
+
+/// Normalizes `text` with the enabled categories.
+///
+/// Categories are applied from the most specific (URLs, dates) to the most
+/// general (plain numbers), so that each sees the text the others left.
+pub fn normalize(text: &str, options: &NormalizeOptions) -> String {
+    let mut text = text.to_string();
+    if options.ruby {
+        text = replace_ruby(&text);
+    }
+    if options.width {
+        text = normalize_width(&text);
+    }
+    if options.urls {
+        text = replace_urls(&text);
+    }
+    if options.dates {
+        text = replace_dates(&text);
+    }
+    if options.times {
+        text = replace_times(&text);
+    }
+    if options.phone_numbers {
+        text = replace_phone_numbers(&text);
+    }
+    if options.currency {
+        text = replace_currency(&text);
+    }
+    if options.numbers || options.units {
+        text = replace_numbers(&text, options);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all(text: &str) -> String {
+        normalize(text, &NormalizeOptions::default())
+    }
+
+    #[test]
+    fn reads_numbers() {
+        assert_eq!(kanji_number("0"), "ゼロ");
+        assert_eq!(kanji_number("1234"), "千二百三十四");
+        assert_eq!(kanji_number("10000"), "一万");
+        assert_eq!(kanji_number("120050003"), "一億二千五万三");
+        assert_eq!(all("人口は1,234,567人"), "人口は百二十三万四千五百六十七人");
+        assert_eq!(all("気温-3.5度"), "気温マイナス三点五度");
+    }
+
+    #[test]
+    fn reads_dates_and_times() {
+        assert_eq!(all("2026/10/17に"), "二千二十六年十月十七日に");
+        assert_eq!(all("2026-04-01"), "二千二十六年四月ついたち");
+        assert_eq!(all("12:30開始"), "十二時三十分開始");
+        assert_eq!(all("9:00"), "九時");
+        assert_eq!(all("2026/13/40"), "二千二十六/十三/四十");
+    }
+
+    #[test]
+    fn reads_units_and_currency() {
+        assert_eq!(all("5km走った"), "五キロメートル走った");
+        assert_eq!(all("25℃、湿度60%"), "二十五度、湿度六十パーセント");
+        assert_eq!(all("¥1,000と$5"), "千円と五ドル");
+        assert_eq!(all("5min"), "五min");
+        assert_eq!(all("5mg"), "五ミリグラム");
+        assert_eq!(all("500mL"), "五百ミリリットル");
+        assert_eq!(all("2m"), "二メートル");
+    }
+
+    #[test]
+    fn reads_phone_numbers_and_urls() {
+        assert_eq!(all("090-1234-5678"), "ゼロキューゼロのイチニーサンヨンのゴーロクナナハチ");
+        assert_eq!(all("https://www.example.com/a?b=1を見て"), " www ドット example ドット com を見て");
+        assert_eq!(all("info@example.jpまで"), " info アットマーク example ドット jp まで");
+    }
+
+    #[test]
+    fn reads_characters() {
+        assert_eq!(read_digits("110"), "イチイチゼロ");
+        assert_eq!(spell_out("NHK 1"), "エヌ エイチ ケー イチ");
+    }
+
+    #[test]
+    fn normalizes_width() {
+        assert_eq!(all("ｶﾞｯｺｳ ﾊﾟﾝ ｳﾞｧ"), "ガッコウ パン ヴァ");
+        assert_eq!(all("１２３ＡＢＣ"), "百二十三ABC");
+    }
+
+    #[test]
+    fn reads_ruby() {
+        assert_eq!(all("｜東北《とうほく》の空"), "とうほくの空");
+        assert_eq!(all("漢字《かんじ》を読む"), "かんじを読む");
+        assert_eq!(all("これは漢字《かんじ》"), "これはかんじ");
+        assert_eq!(all("|東北(とうほく)と《《強調》》"), "とうほくと強調");
+        assert_eq!(all("雨が降る（予報）"), "雨が降る（予報）");
+        assert_eq!(all("ふりがな《ルビ》"), "ふりがな");
+    }
+
+    #[test]
+    fn strips_aozora_notes() {
+        assert_eq!(all("第一章［＃「第一章」は大見出し］\n本文"), "第一章\n本文");
+        assert_eq!(all("※［＃「てへん＋劣」、第3水準1-84-77］る"), "る");
+    }
+
+    #[test]
+    fn categories_can_be_disabled() {
+        let options = NormalizeOptions { units: false, ..Default::default() };
+        assert_eq!(normalize("5km", &options), "五km");
+        assert_eq!(normalize("1,234", &NormalizeOptions::none()), "1,234");
+        assert_eq!(normalize("漢字《かんじ》", &NormalizeOptions::none()), "漢字《かんじ》");
+    }
+}
@@ -0,0 +1,385 @@
+//! Dialogue scripts: lines of several characters, each with their own voice
+//! and variant, rendered into one audio file.
+//!
+//! ```text
+//! # 朝の会話
+//! @gap 0.6
+//! @character ずんだもん speaker=388f246b-8c41-4ac1-8e2d-5d79f3ff56d9 style=ノーマル variant=southern
+//! @character きりたん speaker=東北きりたん variant=northern speed-scale=0.95
+//!
+//! ずんだもん: おはようなのだ。
+//! きりたん[gap=1.2 strength=0.5]: おはよう。
+//!     今日は寒いね。
+//! ```
+//!
+//! `@character` maps a name to a voice, and `@gap` sets the silence between
+//! lines from there on. A line is `名前: セリフ` (or `名前：セリフ`), with
+//! settings in brackets after the name overriding the character's for that
+//! line only; an indented line continues the previous one. Lines starting
+//! with `#` are comments.
+//!
+//! Settings are `key=value` pairs:
+//!
+//! - `speaker`: speaker UUID or name, with `style` (the first style if omitted)
+//! - `id`: speaker ID, instead of `speaker` and `style`
+//! - `variant`: `northern` or `southern`
+//! - `pitch-offset`, `pitch-range`, `speed-scale`: as for plain text
+//! - `strength`: dialect strength, `0.5` or `50%`
+//! - `gap`: silence before the line, in seconds (`1.5`, `1.5s`, `300ms`);
+//!   lines only
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+use crate::error::GenericError;
+use crate::ssml;
+use crate::{EngineError, Speaker, SynthesisOptions, SynthesisParams, SynthesisVariant};
+
+pub type ScriptError = GenericError<String>;
+
+/// Silence between lines, in seconds, unless set otherwise
+pub const DEFAULT_GAP: f64 = 0.5;
+
+/// Voice of a character, or of one of their lines. Unset fields fall back
+/// to the character's, then to the request's.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Voice {
+    /// Speaker UUID or name
+    pub speaker: Option<String>,
+
+    /// Style name of the speaker
+    pub style: Option<String>,
+
+    /// Speaker ID, set directly or by [`Script::resolve_speakers`]
+    pub speaker_id: Option<u32>,
+
+    pub variant: Option<SynthesisVariant>,
+    pub pitch_offset: Option<f64>,
+    pub pitch_range: Option<f64>,
+    pub speed_scale: Option<f64>,
+    pub strength: Option<f64>,
+}
+
+impl Voice {
+    /// `other` on top of `self`. Naming a speaker (or speaker ID) drops the
+    /// one inherited, so that a line can borrow another voice.
+    fn then(&self, other: &Voice) -> Self {
+        let (speaker, speaker_id) = if other.speaker.is_some() || other.speaker_id.is_some() {
+            (other.speaker.clone(), other.speaker_id)
+        } else {
+            (self.speaker.clone(), self.speaker_id)
+        };
+        Self {
+            speaker,
+            style: other.style.clone().or_else(|| self.style.clone()),
+            speaker_id,
+            variant: other.variant.or(self.variant),
+            pitch_offset: other.pitch_offset.or(self.pitch_offset),
+            pitch_range: other.pitch_range.or(self.pitch_range),
+            speed_scale: other.speed_scale.or(self.speed_scale),
+            strength: other.strength.or(self.strength),
+        }
+    }
+
+    /// Sets the speaker ID from the speaker and style names.
+    ///
+    /// A style alone picks that style of the speaker with the current ID.
+    fn resolve(&mut self, speakers: &[Speaker]) -> Result<(), String> {
+        let uuid = match (&self.speaker, self.speaker_id) {
+            (Some(speaker), _) => {
+                let found = speakers.iter().find(|s| s.speaker_uuid == *speaker || s.speaker_name == *speaker);
+                found.ok_or_else(|| format!("unknown speaker {}", speaker))?.speaker_uuid.clone()
+            },
+            (None, Some(id)) if self.style.is_some() => {
+                let found = speakers.iter().find(|s| s.speaker_id == id);
+                found.ok_or_else(|| format!("unknown speaker ID {}", id))?.speaker_uuid.clone()
+            },
+            _ => return Ok(()),
+        };
+        let mut styles = speakers.iter().filter(|s| s.speaker_uuid == uuid).collect::<Vec<_>>();
+        styles.sort_by_key(|s| s.speaker_id);
+        let style = match &self.style {
+            Some(style) => styles.iter().find(|s| s.style_name == *style)
+                .ok_or_else(|| format!("{} has no style {}", styles[0].speaker_name, style))?,
+            None => &styles[0],
+        };
+        self.speaker_id = Some(style.speaker_id);
+        Ok(())
+    }
+
+    /// The request options with this voice applied.
+    ///
+    /// Switching to the other variant uses its default profile, as with
+    /// `<tohoku:dialect>` in SSML.
+    pub fn options(&self, base: &SynthesisOptions) -> Result<SynthesisOptions, EngineError> {
+        let mut options = base.clone();
+        if let Some(variant) = self.variant.filter(|variant| *variant != options.variant) {
+            options.variant = variant;
+            options.profile = variant.default_profile();
+        }
+        options.params = SynthesisParams::new(
+            self.pitch_offset.unwrap_or(base.params.pitch_offset()),
+            self.pitch_range.unwrap_or(base.params.pitch_range()),
+            self.speed_scale.unwrap_or(base.params.speed_scale()),
+        )?;
+        if let Some(strength) = self.strength {
+            options.dialect_strength = strength;
+        }
+        match (self.speaker_id, &self.speaker) {
+            (Some(speaker_id), _) => options.speaker_id = speaker_id,
+            (None, Some(speaker)) => log::warn!("Speaker {} was not resolved, using speaker ID {}", speaker, base.speaker_id),
+            (None, None) => {},
+        }
+        Ok(options)
+    }
+}
+
+/// A line of dialogue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptLine {
+    /// Line number in the script, from 1
+    pub line: usize,
+
+    pub character: String,
+    pub text: String,
+
+    /// The character's voice with the overrides of this line
+    pub voice: Voice,
+
+    /// Silence before the line, in seconds; none before the first line
+    /// unless it sets one
+    pub gap: f64,
+}
+
+/// A parsed script.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Script {
+    pub characters: BTreeMap<String, Voice>,
+    pub lines: Vec<ScriptLine>,
+}
+
+impl Script {
+    /// Resolves speaker names, UUIDs and style names into speaker IDs.
+    pub fn resolve_speakers(&mut self, speakers: &[Speaker]) -> Result<(), ScriptError> {
+        for line in self.lines.iter_mut() {
+            line.voice.resolve(speakers).map_err(|e| error(line.line, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a line is heard in the rendered audio, in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineTiming {
+    /// Line number in the script
+    pub line: usize,
+
+    pub character: String,
+    pub text: String,
+    pub speaker_id: u32,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Timing manifest of a rendered script.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Length of the whole audio, in seconds
+    pub duration: f64,
+
+    pub lines: Vec<LineTiming>,
+}
+
+fn error(line: usize, message: impl std::fmt::Display) -> ScriptError {
+    ScriptError::new(format!("line {}: {}", line, message))
+}
+
+/// Parses a gap: plain seconds, or a duration such as `300ms`.
+fn parse_gap(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok()
+        .filter(|t| t.is_finite() && *t >= 0.0)
+        .or_else(|| ssml::parse_time(value))
+}
+
+fn parse_param(key: &str, value: &str, min: f64, max: f64) -> Result<f64, String> {
+    value.parse::<f64>().ok()
+        .filter(|v| *v > min && *v < max)
+        .ok_or_else(|| format!("{} must be between {} and {}", key, min, max))
+}
+
+/// Parses `key=value` settings separated by spaces or commas, returning
+/// the voice and the gap.
+fn parse_settings(settings: &str) -> Result<(Voice, Option<f64>), String> {
+    let mut voice = Voice::default();
+    let mut gap = None;
+    for setting in settings.split(|c: char| c.is_whitespace() || c == ',').filter(|s| !s.is_empty()) {
+        let (key, value) = setting.split_once('=').ok_or_else(|| format!("expected key=value: {}", setting))?;
+        match key {
+            "speaker" => voice.speaker = Some(value.to_string()),
+            "style" => voice.style = Some(value.to_string()),
+            "id" => voice.speaker_id = Some(value.parse().map_err(|_| "id must be a speaker ID")?),
+            "variant" => {
+                voice.variant = Some(SynthesisVariant::from_str(value, true).map_err(|_| "variant must be northern or southern")?);
+            },
+            "pitch-offset" => {
+                voice.pitch_offset = Some(parse_param(key, value, SynthesisParams::PITCH_OFFSET_MIN, SynthesisParams::PITCH_OFFSET_MAX)?);
+            },
+            "pitch-range" => {
+                voice.pitch_range = Some(parse_param(key, value, SynthesisParams::PITCH_RANGE_MIN, SynthesisParams::PITCH_RANGE_MAX)?);
+            },
+            "speed-scale" => {
+                voice.speed_scale = Some(parse_param(key, value, SynthesisParams::SPEED_SCALE_MIN, SynthesisParams::SPEED_SCALE_MAX)?);
+            },
+            "strength" => voice.strength = Some(ssml::parse_strength(value).ok_or("strength must be between 0 and 1")?),
+            "gap" => gap = Some(parse_gap(value).ok_or("gap must be a duration in seconds")?),
+            _ => return Err(format!("unknown setting {}", key)),
+        }
+    }
+    Ok((voice, gap))
+}
+
+/// Splits `名前[settings]` into the name and the settings.
+fn parse_speaker(head: &str) -> Result<(&str, &str), String> {
+    let head = head.trim();
+    match head.split_once('[') {
+        Some((name, settings)) => {
+            let settings = settings.strip_suffix(']').ok_or("settings after a name must end with ]")?;
+            Ok((name.trim(), settings))
+        },
+        None => Ok((head, "")),
+    }
+}
+
+/// Parses a script; `gap` is the silence between lines until the script
+/// sets `@gap`.
+pub fn parse(script: &str, gap: f64) -> Result<Script, ScriptError> {
+    let mut result = Script::default();
+    let mut gap = gap;
+    for (n, raw) in script.trim_start_matches('\u{FEFF}').lines().enumerate() {
+        let n = n + 1;
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(directive) = line.strip_prefix('@') {
+            let (name, args) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+            match name {
+                "gap" => gap = parse_gap(args).ok_or_else(|| error(n, "gap must be a duration in seconds"))?,
+                "character" => {
+                    let args = args.trim();
+                    let (character, settings) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                    if character.is_empty() {
+                        return Err(error(n, "@character needs a name"));
+                    }
+                    let (voice, line_gap) = parse_settings(settings).map_err(|e| error(n, e))?;
+                    if line_gap.is_some() {
+                        return Err(error(n, "gap can only be set on a line"));
+                    }
+                    result.characters.insert(character.to_string(), voice);
+                },
+                _ => return Err(error(n, format!("unknown directive @{}", name))),
+            }
+            continue;
+        }
+
+        if raw.starts_with(char::is_whitespace) {
+            let prev = result.lines.last_mut().ok_or_else(|| error(n, "indented line with nothing to continue"))?;
+            if !prev.text.is_empty() {
+                prev.text.push('\n');
+            }
+            prev.text.push_str(line);
+            continue;
+        }
+
+        let (head, text) = line.split_once([':', '：']).ok_or_else(|| error(n, "expected 名前: セリフ"))?;
+        let (name, settings) = parse_speaker(head).map_err(|e| error(n, e))?;
+        let voice = result.characters.get(name).ok_or_else(|| error(n, format!("unknown character {}", name)))?;
+        let (overrides, line_gap) = parse_settings(settings).map_err(|e| error(n, e))?;
+        let first = result.lines.is_empty();
+        result.lines.push(ScriptLine {
+            line: n,
+            character: name.to_string(),
+            text: text.trim().to_string(),
+            voice: voice.then(&overrides),
+            gap: line_gap.unwrap_or(if first { 0.0 } else { gap }),
+        });
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "\
+# 朝の会話
+@gap 0.6
+@character ずんだもん speaker=zunda style=あまあま variant=southern
+@character きりたん id=108 speed-scale=0.95
+
+ずんだもん: おはようなのだ。
+きりたん[gap=1.2, strength=50%]：おはよう。
+    今日は寒いね。
+ずんだもん [speaker=kiritan]: 声を借りたのだ。
+";
+
+    fn speaker(speaker_id: u32, name: &str, style_name: &str) -> Speaker {
+        Speaker {
+            speaker_id,
+            speaker_name: name.to_string(),
+            speaker_uuid: format!("uuid-{}", name),
+            style_name: style_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_lines_with_overrides() {
+        let script = parse(SCRIPT, DEFAULT_GAP).unwrap();
+        assert_eq!(script.characters.len(), 2);
+        let lines = &script.lines;
+        assert_eq!(lines.iter().map(|l| (l.line, l.character.as_str(), l.text.as_str(), l.gap)).collect::<Vec<_>>(), vec![
+            (6, "ずんだもん", "おはようなのだ。", 0.0),
+            (7, "きりたん", "おはよう。\n今日は寒いね。", 1.2),
+            (9, "ずんだもん", "声を借りたのだ。", 0.6),
+        ]);
+        assert_eq!(lines[0].voice.variant, Some(SynthesisVariant::Southern));
+        assert_eq!(lines[1].voice.speed_scale, Some(0.95));
+        assert_eq!(lines[1].voice.strength, Some(0.5));
+        assert_eq!(lines[2].voice.speaker.as_deref(), Some("kiritan"));
+        assert_eq!(lines[2].voice.style.as_deref(), Some("あまあま"));
+        assert_eq!(lines[2].voice.variant, Some(SynthesisVariant::Southern));
+    }
+
+    #[test]
+    fn resolves_speakers() {
+        let speakers = [
+            speaker(3, "zunda", "ノーマル"),
+            speaker(1, "zunda", "あまあま"),
+            speaker(108, "kiritan", "ノーマル"),
+            speaker(110, "kiritan", "あまあま"),
+        ];
+        let mut script = parse(SCRIPT, DEFAULT_GAP).unwrap();
+        script.resolve_speakers(&speakers).unwrap();
+        assert_eq!(script.lines.iter().map(|l| l.voice.speaker_id).collect::<Vec<_>>(), vec![Some(1), Some(108), Some(110)]);
+
+        let mut script = parse("@character a speaker=uuid-zunda\na: はい", DEFAULT_GAP).unwrap();
+        script.resolve_speakers(&speakers).unwrap();
+        assert_eq!(script.lines[0].voice.speaker_id, Some(1));
+
+        let mut script = parse("@character a speaker=zunda style=ささやき\n\na: はい", DEFAULT_GAP).unwrap();
+        assert_eq!(script.resolve_speakers(&speakers).unwrap_err().to_string(), "line 3: zunda has no style ささやき");
+    }
+
+    #[test]
+    fn reports_errors_with_line() {
+        let err = |script: &str| parse(script, DEFAULT_GAP).unwrap_err().to_string();
+        assert_eq!(err("@character a\n\nb: はい"), "line 3: unknown character b");
+        assert_eq!(err("@character a variant=western"), "line 1: variant must be northern or southern");
+        assert_eq!(err("@character a\na[speed-scale=0]: はい"), "line 2: speed-scale must be between 0.01 and 100");
+        assert_eq!(err("  続き"), "line 1: indented line with nothing to continue");
+        assert_eq!(err("@character a\nこんにちは"), "line 2: expected 名前: セリフ");
+    }
+}
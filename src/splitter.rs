@@ -0,0 +1,519 @@
+use crate::lexical;
+
+/// Sentence delimiters used unless configured otherwise
+pub const DEFAULT_DELIMITERS: &[char] = &['。', '｡', '．', '？', '?', '！', '!', '…', '♪', '\n'];
+
+/// Closing brackets kept with the sentence they close (「行く。」)
+const CLOSING_BRACKETS: &[char] = &['」', '』', '）', ')', '】', '〉', '》', '］', ']', '"', '”'];
+
+/// Opening brackets and quotes with their closing counterparts
+const BRACKETS: &[(char, char)] = &[
+    ('「', '」'), ('『', '』'), ('（', '）'), ('(', ')'), ('【', '】'), ('〈', '〉'),
+    ('《', '》'), ('［', '］'), ('[', ']'), ('“', '”'), ('"', '"'),
+];
+
+/// Quotes longer than this (in characters) are split like plain text
+pub const DEFAULT_MAX_QUOTE_LENGTH: usize = 200;
+
+/// Sentences longer than this (in characters) are split at clause boundaries
+pub const DEFAULT_MAX_LENGTH: usize = 100;
+
+/// Clause boundaries tried in order when a sentence is too long: commas,
+/// then conjunctive particles, then spaces
+const CLAUSE_COMMAS: &[char] = &['、', '，', ','];
+const CONJUNCTIVE_PARTICLES: &[&str] = &["けれども", "けれど", "けど", "ので", "のに", "から", "ながら", "たら", "ても"];
+
+/// Whether the bracket span starting at `start` is ruby (東北《とうほく》) or
+/// an Aozora Bunko note (［＃改ページ］): these stay in the plain text.
+fn is_annotation(chars: &[char], start: usize) -> bool {
+    chars[start] == '《' || (chars[start] == '［' && chars.get(start + 1) == Some(&'＃'))
+}
+
+/// Returns where the base text of the ruby opened at `open` starts: at an
+/// explicit `｜`, or else at the start of the run of kanji before it.
+fn ruby_base_start(chars: &[char], open: usize) -> usize {
+    let word = chars[..open].iter()
+        .rposition(|c| c.is_whitespace() || matches!(c, '》' | '］'))
+        .map_or(0, |k| k + 1);
+    if let Some(bar) = chars[word..open].iter().rposition(|c| matches!(c, '｜' | '|')) {
+        return word + bar;
+    }
+    chars[word..open].iter().rposition(|c| !lexical::is_kanji(*c)).map_or(word, |k| word + k + 1)
+}
+
+/// Returns, for each position of `chars` (and the end), whether a sentence
+/// may be cut before it: not inside an annotation or between ruby and its
+/// base text.
+fn cut_points(chars: &[char]) -> Vec<bool> {
+    let mut allowed = vec![true; chars.len() + 1];
+    let mut i = 0;
+    while i < chars.len() {
+        let closer = if chars[i] == '《' { '》' } else { '］' };
+        let end = chars[i..].iter().position(|c| *c == closer).filter(|_| is_annotation(chars, i));
+        let Some(end) = end.map(|n| i + n) else {
+            i += 1;
+            continue;
+        };
+        let start = match chars[i] {
+            '《' => ruby_base_start(chars, i).min(i.saturating_sub(1)),
+            _ => i,
+        };
+        allowed[start + 1..=end].fill(false);
+        i = end + 1;
+    }
+    allowed
+}
+
+/// Characters that continue a sentence after a closing quote (「行く。」と言った, 「行く」って)
+const QUOTATIVE: &[char] = &['と', 'っ'];
+
+/// Whether a part of a sentence is inside brackets or quotes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PartKind {
+    Plain,
+
+    /// A bracketed or quoted span, brackets included
+    Quote,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentencePart {
+    pub text: String,
+    pub kind: PartKind,
+}
+
+/// A sentence, split into plain and quoted parts.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Sentence {
+    pub parts: Vec<SentencePart>,
+
+    /// Set on every piece but the first of an over-long sentence split at
+    /// clause boundaries; it should follow the previous one with a short pause
+    pub continues: bool,
+}
+
+impl Sentence {
+    pub fn text(&self) -> String {
+        self.parts.iter().map(|part| part.text.as_str()).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.parts.iter().map(|part| part.text.chars().count()).sum()
+    }
+
+    /// Splits off the parts after character `at`.
+    fn split_off(&mut self, at: usize) -> Sentence {
+        let mut rest = Sentence {
+            parts: Vec::new(),
+            continues: true,
+        };
+        let mut offset = 0;
+        for part in std::mem::take(&mut self.parts) {
+            let len = part.text.chars().count();
+            if offset + len <= at {
+                self.parts.push(part);
+            } else if offset >= at {
+                rest.parts.push(part);
+            } else {
+                let head = part.text.chars().take(at - offset).collect::<String>();
+                let tail = part.text.chars().skip(at - offset).collect::<String>();
+                self.push(head, part.kind);
+                rest.push(tail, part.kind);
+            }
+            offset += len;
+        }
+        rest
+    }
+
+    fn push(&mut self, text: String, kind: PartKind) {
+        if text.is_empty() {
+            return;
+        }
+        match self.parts.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(&text),
+            _ => self.parts.push(SentencePart { text, kind }),
+        }
+    }
+}
+
+/// Splits text into sentences for synthesis.
+///
+/// Delimiters stay attached to their sentence, so `？` still reaches Open
+/// JTalk. A run of delimiters (`！？`, `。。。`) ends a single sentence.
+#[derive(Debug, Clone)]
+pub struct TextSplitter {
+    delimiters: Vec<char>,
+    max_quote_length: usize,
+    max_length: Option<usize>,
+}
+
+impl Default for TextSplitter {
+    fn default() -> Self {
+        TextSplitterBuilder::new().build()
+    }
+}
+
+/// Configures a `TextSplitter`.
+#[derive(Debug, Clone)]
+pub struct TextSplitterBuilder {
+    delimiters: Vec<char>,
+    max_quote_length: usize,
+    max_length: Option<usize>,
+}
+
+impl Default for TextSplitterBuilder {
+    fn default() -> Self {
+        Self {
+            delimiters: DEFAULT_DELIMITERS.to_vec(),
+            max_quote_length: DEFAULT_MAX_QUOTE_LENGTH,
+            max_length: Some(DEFAULT_MAX_LENGTH),
+        }
+    }
+}
+
+impl TextSplitterBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the delimiter set.
+    pub fn delimiters(mut self, delimiters: impl IntoIterator<Item = char>) -> Self {
+        self.delimiters = delimiters.into_iter().collect();
+        self
+    }
+
+    /// Adds a delimiter to the set.
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        if !self.delimiters.contains(&delimiter) {
+            self.delimiters.push(delimiter);
+        }
+        self
+    }
+
+    /// Sets the length (in characters) above which quotes are split inside.
+    pub fn max_quote_length(mut self, max_quote_length: usize) -> Self {
+        self.max_quote_length = max_quote_length;
+        self
+    }
+
+    /// Sets the length (in characters) above which sentences are split at
+    /// clause boundaries; `None` never splits them.
+    pub fn max_length(mut self, max_length: Option<usize>) -> Self {
+        self.max_length = max_length.filter(|max| *max > 0);
+        self
+    }
+
+    pub fn build(self) -> TextSplitter {
+        TextSplitter {
+            delimiters: self.delimiters,
+            max_quote_length: self.max_quote_length,
+            max_length: self.max_length,
+        }
+    }
+}
+
+impl TextSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builder() -> TextSplitterBuilder {
+        TextSplitterBuilder::new()
+    }
+
+    fn is_delimiter(&self, c: char) -> bool {
+        self.delimiters.contains(&c)
+    }
+
+    /// Returns the outermost balanced bracket spans of `chars` no longer
+    /// than the maximum quote length, as inclusive index ranges.
+    ///
+    /// Unmatched openers and closers are left as plain characters.
+    fn quote_spans(&self, chars: &[char]) -> Vec<(usize, usize)> {
+        let mut stack: Vec<(usize, char)> = Vec::new();
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        for (i, &c) in chars.iter().enumerate() {
+            if let Some(depth) = stack.iter().rposition(|(_, closer)| *closer == c) {
+                // openers left above the match were never closed
+                let (start, _) = stack[depth];
+                stack.truncate(depth);
+                if i - start - 1 <= self.max_quote_length {
+                    spans.retain(|(s, _)| *s < start);
+                    spans.push((start, i));
+                }
+            } else if let Some((_, closer)) = BRACKETS.iter().find(|(opener, _)| *opener == c) {
+                stack.push((i, *closer));
+            }
+        }
+        spans
+    }
+
+    /// Splits `text` into sentences, keeping bracketed and quoted spans
+    /// (「行くの？」と聞いた) within one sentence.
+    ///
+    /// Ruby and Aozora Bunko notes are never split and stay plain text, so
+    /// that normalization can read them later.
+    ///
+    /// Sentences are trimmed; the ones made of delimiters and whitespace
+    /// only are dropped.
+    pub fn split_sentences(&self, text: &str) -> Vec<Sentence> {
+        let chars = text.chars().collect::<Vec<char>>();
+        let spans = self.quote_spans(&chars);
+        let mut spans = spans.iter().peekable();
+
+        let mut sentences = Vec::new();
+        let mut sentence = Sentence::default();
+        let mut current = String::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            if let Some(&&(start, end)) = spans.peek() {
+                if start == i && is_annotation(&chars, start) {
+                    spans.next();
+                    current.extend(&chars[start..=end]);
+                    i = end + 1;
+                    continue;
+                }
+                if start == i {
+                    spans.next();
+                    sentence.push(std::mem::take(&mut current), PartKind::Plain);
+                    sentence.push(chars[start..=end].iter().collect(), PartKind::Quote);
+                    i = end + 1;
+
+                    // 「行く。」 ends a sentence unless a quotative particle (or a
+                    // lower-case word, for English) follows
+                    let ends_with_delimiter = end > start + 1 && self.is_delimiter(chars[end - 1]);
+                    let next = chars[i..].iter().find(|c| !c.is_whitespace());
+                    if ends_with_delimiter && !next.is_some_and(|c| QUOTATIVE.contains(c) || c.is_ascii_lowercase()) {
+                        self.push_sentence(&mut sentences, &mut sentence, &mut current);
+                    }
+                    continue;
+                }
+            }
+
+            i += 1;
+            if !self.is_delimiter(c) {
+                current.push(c);
+                continue;
+            }
+
+            // the whole run of delimiters and closing brackets ends this sentence
+            let mut next = Some(c);
+            while let Some(c) = next {
+                if !c.is_whitespace() {
+                    current.push(c);
+                }
+                next = chars.get(i).copied().filter(|c| self.is_delimiter(*c) || CLOSING_BRACKETS.contains(c));
+                if next.is_some() {
+                    i += 1;
+                }
+            }
+            self.push_sentence(&mut sentences, &mut sentence, &mut current);
+        }
+        self.push_sentence(&mut sentences, &mut sentence, &mut current);
+
+        sentences
+    }
+
+    /// Returns the text of each sentence of `text`; see `split_sentences`.
+    pub fn split_text(&self, text: &str) -> Vec<String> {
+        self.split_sentences(text).iter().map(Sentence::text).collect()
+    }
+
+    fn has_content(&self, text: &str) -> bool {
+        text.chars().any(|c| !c.is_whitespace() && !self.is_delimiter(c) && !CLOSING_BRACKETS.contains(&c))
+    }
+
+    fn push_sentence(&self, sentences: &mut Vec<Sentence>, sentence: &mut Sentence, current: &mut String) {
+        sentence.push(std::mem::take(current), PartKind::Plain);
+        let mut parts = std::mem::take(&mut sentence.parts);
+
+        // trim the sentence, and fold parts without content into their neighbours
+        if let Some(first) = parts.first_mut() {
+            first.text = first.text.trim_start().to_string();
+        }
+        if let Some(last) = parts.last_mut() {
+            last.text = last.text.trim_end().to_string();
+        }
+        let mut result = Sentence::default();
+        for part in parts {
+            if part.text.is_empty() {
+                continue;
+            }
+            match result.parts.last_mut() {
+                Some(last) if !self.has_content(&part.text) => last.text.push_str(&part.text),
+                _ => result.parts.push(part),
+            }
+        }
+        if !result.parts.iter().any(|part| self.has_content(&part.text)) {
+            return;
+        }
+
+        let Some(max) = self.max_length else {
+            sentences.push(result);
+            return;
+        };
+        while result.len() > max {
+            let chars = result.text().chars().collect::<Vec<char>>();
+            let rest = result.split_off(Self::clause_cut(&chars, max));
+            self.push_piece(sentences, result);
+            result = rest;
+        }
+        self.push_piece(sentences, result);
+    }
+
+    /// Pushes a piece of a split sentence, trimmed, unless it has no content.
+    fn push_piece(&self, sentences: &mut Vec<Sentence>, mut piece: Sentence) {
+        if let Some(first) = piece.parts.first_mut() {
+            first.text = first.text.trim_start().to_string();
+        }
+        if let Some(last) = piece.parts.last_mut() {
+            last.text = last.text.trim_end().to_string();
+        }
+        piece.parts.retain(|part| !part.text.is_empty());
+        if !piece.parts.iter().any(|part| self.has_content(&part.text)) {
+            // punctuation left over from a cut belongs to the previous piece
+            if let Some(prev) = sentences.last_mut() {
+                for part in piece.parts {
+                    prev.push(part.text, part.kind);
+                }
+            }
+            return;
+        }
+        sentences.push(piece);
+    }
+
+    /// Returns where to cut an over-long sentence: after the last clause
+    /// boundary within `max` characters, or at `max` if there is none.
+    ///
+    /// Annotations are never cut; one running past `max` moves the cut after it.
+    fn clause_cut(chars: &[char], max: usize) -> usize {
+        let limit = max.min(chars.len() - 1);
+        let allowed = cut_points(chars);
+        let after_comma = |k: usize| CLAUSE_COMMAS.contains(&chars[k - 1]);
+        let after_particle = |k: usize| CONJUNCTIVE_PARTICLES.iter().any(|particle| {
+            let len = particle.chars().count();
+            k > len && chars[k - len..k].iter().copied().eq(particle.chars())
+        });
+        let after_space = |k: usize| chars[k - 1].is_whitespace();
+
+        let boundaries: [&dyn Fn(usize) -> bool; 3] = [&after_comma, &after_particle, &after_space];
+        boundaries.iter()
+            .find_map(|is_boundary| (1..=limit).rev().find(|k| allowed[*k] && is_boundary(*k)))
+            .or_else(|| (1..=limit).rev().find(|k| allowed[*k]))
+            .or_else(|| (limit + 1..chars.len()).find(|k| allowed[*k]))
+            .unwrap_or(limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_delimiters() {
+        let splitter = TextSplitter::new();
+        assert_eq!(splitter.split_text("行く？行かない。"), vec!["行く？", "行かない。"]);
+        assert_eq!(splitter.split_text("Really?Yes!"), vec!["Really?", "Yes!"]);
+        assert_eq!(splitter.split_text("ラララ♪たのしい…"), vec!["ラララ♪", "たのしい…"]);
+    }
+
+    #[test]
+    fn merges_repeated_punctuation() {
+        let splitter = TextSplitter::new();
+        assert_eq!(splitter.split_text("本当！？うそ。。。"), vec!["本当！？", "うそ。。。"]);
+        assert_eq!(splitter.split_text("えっ……そう"), vec!["えっ……", "そう"]);
+    }
+
+    #[test]
+    fn trims_lines_and_drops_empty_sentences() {
+        let splitter = TextSplitter::new();
+        assert_eq!(splitter.split_text("  一行目\n\n二行目。\n"), vec!["一行目", "二行目。"]);
+        assert_eq!(splitter.split_text("。！\n？"), Vec::<String>::new());
+        assert_eq!(splitter.split_text("すごい\n！"), vec!["すごい！"]);
+    }
+
+    #[test]
+    fn keeps_closing_brackets() {
+        let splitter = TextSplitter::new();
+        assert_eq!(splitter.split_text("「行く。」次。"), vec!["「行く。」", "次。"]);
+    }
+
+    #[test]
+    fn builder_configures_delimiters() {
+        let splitter = TextSplitter::builder().delimiters(['。']).delimiter('、').build();
+        assert_eq!(splitter.split_text("はい、そうです？いいえ。"), vec!["はい、", "そうです？いいえ。"]);
+    }
+
+    #[test]
+    fn keeps_quotes_together() {
+        let splitter = TextSplitter::new();
+        assert_eq!(splitter.split_text("「行くの？」と聞いた。はい。"), vec!["「行くの？」と聞いた。", "はい。"]);
+        assert_eq!(splitter.split_text("『本当？（嘘！）』って"), vec!["『本当？（嘘！）』って"]);
+        assert_eq!(splitter.split_text("He said \"Go! Now!\" loudly."), vec!["He said \"Go! Now!\" loudly."]);
+    }
+
+    #[test]
+    fn sentence_parts() {
+        let splitter = TextSplitter::new();
+        let sentences = splitter.split_sentences("彼は「行く。」と言った。");
+        assert_eq!(sentences[0].parts, vec![
+            SentencePart { text: "彼は".to_string(), kind: PartKind::Plain },
+            SentencePart { text: "「行く。」".to_string(), kind: PartKind::Quote },
+            SentencePart { text: "と言った。".to_string(), kind: PartKind::Plain },
+        ]);
+    }
+
+    #[test]
+    fn handles_unbalanced_brackets() {
+        let splitter = TextSplitter::new();
+        assert_eq!(splitter.split_text("「行く。来る。"), vec!["「行く。", "来る。"]);
+        assert_eq!(splitter.split_text("行く」。来る）。"), vec!["行く」。", "来る）。"]);
+        assert_eq!(splitter.split_text("「（行く。」来る。"), vec!["「（行く。」", "来る。"]);
+    }
+
+    #[test]
+    fn splits_long_quotes() {
+        let splitter = TextSplitter::builder().max_quote_length(5).build();
+        assert_eq!(splitter.split_text("「はい。いいえ。」"), vec!["「はい。", "いいえ。」"]);
+        assert_eq!(splitter.split_text("「はい。」"), vec!["「はい。」"]);
+    }
+
+    #[test]
+    fn splits_long_sentences_at_clauses() {
+        let splitter = TextSplitter::builder().max_length(Some(10)).build();
+        let sentences = splitter.split_sentences("雨が降ったので、家にいたけど退屈だった。");
+        let texts = sentences.iter().map(Sentence::text).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["雨が降ったので、", "家にいたけど", "退屈だった。"]);
+        assert_eq!(sentences.iter().map(|s| s.continues).collect::<Vec<_>>(), vec![false, true, true]);
+
+        assert_eq!(splitter.split_text("one two three four"), vec!["one two", "three four"]);
+        assert_eq!(splitter.split_text("あいうえおかきくけこさしす"), vec!["あいうえおかきくけこ", "さしす"]);
+        assert_eq!(TextSplitter::builder().max_length(None).build().split_text("あいうえおかきくけこさしす"), vec!["あいうえおかきくけこさしす"]);
+    }
+
+    #[test]
+    fn long_sentences_keep_parts() {
+        let splitter = TextSplitter::builder().max_length(Some(11)).build();
+        let sentences = splitter.split_sentences("彼は「行くよ」と、言った。");
+        assert_eq!(sentences[0].parts[1], SentencePart { text: "「行くよ」".to_string(), kind: PartKind::Quote });
+        assert_eq!(sentences[1].text(), "言った。");
+    }
+
+    #[test]
+    fn keeps_annotations_in_plain_text() {
+        let splitter = TextSplitter::new();
+        let sentences = splitter.split_sentences("｜東北《とうほく》は寒い［＃「寒い」に傍点］。雪《ゆき？》だ！");
+        assert_eq!(sentences.iter().map(Sentence::text).collect::<Vec<_>>(), vec!["｜東北《とうほく》は寒い［＃「寒い」に傍点］。", "雪《ゆき？》だ！"]);
+        assert!(sentences.iter().flat_map(|s| &s.parts).all(|part| part.kind == PartKind::Plain));
+    }
+
+    #[test]
+    fn never_cuts_ruby() {
+        let splitter = TextSplitter::builder().max_length(Some(10)).build();
+        assert_eq!(splitter.split_text("あいう、東北地方《とうほくちほう》です"), vec!["あいう、", "東北地方《とうほくちほう》", "です"]);
+        assert_eq!(splitter.split_text("あいうえおか｜東北《とうほく》"), vec!["あいうえおか", "｜東北《とうほく》"]);
+    }
+}
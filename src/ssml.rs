@@ -0,0 +1,439 @@
+//! A subset of SSML 1.1 as an alternative to plain text input.
+//!
+//! Supported elements are `<speak>`, `<break>`, `<prosody>`, `<emphasis>`,
+//! `<sub>`, `<say-as>`, `<phoneme>` (with kana notation), `<audio>` (local
+//! WAV files) and the vendor element `<tohoku:dialect>`, which switches the
+//! variant and dialect strength for its content:
+//!
+//! ```xml
+//! <speak xmlns:tohoku="https://github.com/metastable-void/tohoku-tts-voicevox/ssml">
+//!   標準語では<break time="300ms"/>
+//!   <tohoku:dialect variant="southern" strength="0.5">少しなまって</tohoku:dialect>
+//! </speak>
+//! ```
+//!
+//! Unknown elements are read as their content.
+
+use clap::ValueEnum;
+
+use std::path::PathBuf;
+
+use crate::error::GenericError;
+use crate::normalize::{self, NormalizeOptions};
+use crate::types;
+use crate::SynthesisVariant;
+
+pub type SsmlError = GenericError<String>;
+
+/// Namespace of the vendor extension elements
+pub const VENDOR_NAMESPACE: &str = "https://github.com/metastable-void/tohoku-tts-voicevox/ssml";
+
+/// Pause of `<break>` without attributes, in seconds
+const DEFAULT_BREAK: f64 = 0.4;
+
+/// Changes to the delivery of a part of the document, relative to the request.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Prosody {
+    /// Factor on the speed scale
+    pub rate: f64,
+
+    /// Pitch shift in semitones
+    pub pitch: f64,
+
+    /// Volume change in decibels
+    pub volume: f64,
+
+    /// Factor on the intonation scale
+    pub intonation: f64,
+}
+
+impl Default for Prosody {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            pitch: 0.0,
+            volume: 0.0,
+            intonation: 1.0,
+        }
+    }
+}
+
+impl Prosody {
+    /// `other` nested inside `self`
+    fn then(&self, other: &Prosody) -> Self {
+        Self {
+            rate: self.rate * other.rate,
+            pitch: self.pitch + other.pitch,
+            volume: self.volume + other.volume,
+            intonation: self.intonation * other.intonation,
+        }
+    }
+
+    pub fn apply(&self, query: &mut types::AudioQuery) {
+        query.speed_scale *= self.rate;
+        query.volume_scale *= 10f64.powf(self.volume / 20.0);
+        query.intonation_scale *= self.intonation;
+
+        // shifted in log-F0 so the pitch scale of the query keeps its meaning
+        let shift = self.pitch * std::f64::consts::LN_2 / 12.0;
+        if shift != 0.0 {
+            for phrase in query.accent_phrases.iter_mut() {
+                for mora in phrase.moras.iter_mut().filter(|mora| mora.pitch > 0.0) {
+                    mora.pitch += shift;
+                }
+            }
+        }
+    }
+}
+
+/// Everything inherited from the enclosing elements.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct SsmlStyle {
+    pub prosody: Prosody,
+
+    /// Variant set by `<tohoku:dialect>`; `None` keeps the request's
+    pub variant: Option<SynthesisVariant>,
+
+    /// Dialect strength set by `<tohoku:dialect>`; `None` keeps the request's
+    pub strength: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SsmlItem {
+    /// Text read through the usual pipeline
+    Text {
+        text: String,
+        style: SsmlStyle,
+    },
+
+    /// Reading in the kana notation of `audio_query` (AquesTalk style)
+    Kana {
+        kana: String,
+        style: SsmlStyle,
+    },
+
+    /// Silence, in seconds
+    Break(f64),
+
+    /// A local WAV file, with text to read if it cannot be played
+    Audio {
+        src: PathBuf,
+        fallback: String,
+        style: SsmlStyle,
+    },
+}
+
+/// A parsed document: a flat sequence of things to say.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SsmlDocument {
+    pub items: Vec<SsmlItem>,
+}
+
+impl SsmlDocument {
+    fn push_text(&mut self, text: &str, style: &SsmlStyle) {
+        if let Some(SsmlItem::Text { text: last, style: last_style }) = self.items.last_mut() {
+            if last_style == style {
+                last.push_str(text);
+                return;
+            }
+        }
+        self.items.push(SsmlItem::Text {
+            text: text.to_string(),
+            style: *style,
+        });
+    }
+
+    /// Collapses whitespace and drops text items left empty.
+    fn tidy(mut self) -> Self {
+        for item in self.items.iter_mut() {
+            if let SsmlItem::Text { text, .. } = item {
+                *text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            }
+        }
+        self.items.retain(|item| !matches!(item, SsmlItem::Text { text, .. } if text.is_empty()));
+        self
+    }
+}
+
+fn error(node: &roxmltree::Node, message: &str) -> SsmlError {
+    let pos = node.document().text_pos_at(node.range().start);
+    SsmlError::new(format!("{}:{}: <{}>: {}", pos.row, pos.col, node.tag_name().name(), message))
+}
+
+/// Parses a duration such as `500ms` or `1.5s`.
+pub(crate) fn parse_time(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let (number, scale) = match value.strip_suffix("ms") {
+        Some(number) => (number, 0.001),
+        None => (value.strip_suffix('s')?, 1.0),
+    };
+    number.trim().parse::<f64>().ok().filter(|t| t.is_finite() && *t >= 0.0).map(|t| t * scale)
+}
+
+/// Parses a dialect strength, `0.5` or `50%`, between 0 and 1.
+pub(crate) fn parse_strength(value: &str) -> Option<f64> {
+    let strength = match value.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().ok().map(|p| p / 100.0),
+        None => value.trim().parse::<f64>().ok(),
+    };
+    strength.filter(|s| (0.0..=1.0).contains(s))
+}
+
+/// Parses `+10%` / `-10%` as a factor.
+fn parse_relative_percent(value: &str) -> Option<f64> {
+    let number = value.strip_suffix('%')?.trim().parse::<f64>().ok()?;
+    Some(1.0 + number / 100.0).filter(|f| *f > 0.0)
+}
+
+fn parse_rate(value: &str) -> Option<f64> {
+    let rate = match value {
+        "x-slow" => 0.5,
+        "slow" => 0.75,
+        "medium" | "default" => 1.0,
+        "fast" => 1.25,
+        "x-fast" => 1.5,
+        _ if value.starts_with(['+', '-']) => parse_relative_percent(value)?,
+        _ => match value.strip_suffix('%') {
+            Some(number) => number.parse::<f64>().ok()? / 100.0,
+            None => value.parse().ok()?,
+        },
+    };
+    Some(rate).filter(|r| r.is_finite() && *r > 0.0)
+}
+
+fn parse_pitch(value: &str) -> Option<f64> {
+    let semitones = match value {
+        "x-low" => -4.0,
+        "low" => -2.0,
+        "medium" | "default" => 0.0,
+        "high" => 2.0,
+        "x-high" => 4.0,
+        _ => match value.strip_suffix("st") {
+            Some(number) => number.parse().ok()?,
+            None => 12.0 * parse_relative_percent(value)?.log2(),
+        },
+    };
+    Some(semitones).filter(|st: &f64| st.is_finite())
+}
+
+fn parse_volume(value: &str) -> Option<f64> {
+    let db = match value {
+        "silent" => -96.0,
+        "x-soft" => -12.0,
+        "soft" => -6.0,
+        "medium" | "default" => 0.0,
+        "loud" => 6.0,
+        "x-loud" => 12.0,
+        _ => match value.strip_suffix("dB") {
+            Some(number) => number.parse().ok()?,
+            None => 20.0 * parse_relative_percent(value)?.log10(),
+        },
+    };
+    Some(db).filter(|db: &f64| db.is_finite())
+}
+
+fn prosody(node: &roxmltree::Node) -> Result<Prosody, SsmlError> {
+    let mut prosody = Prosody::default();
+    if let Some(value) = node.attribute("rate") {
+        prosody.rate = parse_rate(value).ok_or_else(|| error(node, "invalid rate"))?;
+    }
+    if let Some(value) = node.attribute("pitch") {
+        prosody.pitch = parse_pitch(value).ok_or_else(|| error(node, "invalid pitch (use st, % or a name)"))?;
+    }
+    if let Some(value) = node.attribute("volume") {
+        prosody.volume = parse_volume(value).ok_or_else(|| error(node, "invalid volume (use dB, % or a name)"))?;
+    }
+    Ok(prosody)
+}
+
+fn emphasis(node: &roxmltree::Node) -> Result<Prosody, SsmlError> {
+    let (intonation, rate, volume) = match node.attribute("level").unwrap_or("moderate") {
+        "strong" => (1.4, 0.9, 2.0),
+        "moderate" => (1.2, 0.95, 1.0),
+        "reduced" => (0.8, 1.0, -3.0),
+        "none" => (1.0, 1.0, 0.0),
+        _ => return Err(error(node, "invalid level")),
+    };
+    Ok(Prosody { rate, pitch: 0.0, volume, intonation })
+}
+
+fn break_time(node: &roxmltree::Node) -> Result<f64, SsmlError> {
+    if let Some(time) = node.attribute("time") {
+        return parse_time(time).ok_or_else(|| error(node, "invalid time (use s or ms)"));
+    }
+    Ok(match node.attribute("strength") {
+        None => DEFAULT_BREAK,
+        Some("none") => 0.0,
+        Some("x-weak") => 0.1,
+        Some("weak") => 0.2,
+        Some("medium") => DEFAULT_BREAK,
+        Some("strong") => 0.7,
+        Some("x-strong") => 1.2,
+        Some(_) => return Err(error(node, "invalid strength")),
+    })
+}
+
+fn say_as(node: &roxmltree::Node, text: &str) -> Result<String, SsmlError> {
+    let text = text.trim();
+    let only = |options: NormalizeOptions| normalize::normalize(text, &NormalizeOptions { width: true, numbers: true, ..options });
+    Ok(match node.attribute("interpret-as") {
+        Some("cardinal" | "number") => normalize::read_number(text),
+        Some("ordinal") => format!("第{}", normalize::read_number(text)),
+        Some("digits") => normalize::read_digits(text),
+        Some("telephone") => normalize::read_digits(text),
+        Some("characters" | "spell-out") => normalize::spell_out(text),
+        Some("date") => only(NormalizeOptions { dates: true, ..NormalizeOptions::none() }),
+        Some("time") => only(NormalizeOptions { times: true, ..NormalizeOptions::none() }),
+        Some(other) => {
+            log::warn!("Unsupported say-as interpret-as={}, reading as written", other);
+            text.to_string()
+        },
+        None => return Err(error(node, "missing interpret-as")),
+    })
+}
+
+fn dialect(node: &roxmltree::Node, style: &SsmlStyle) -> Result<SsmlStyle, SsmlError> {
+    let mut style = *style;
+    if let Some(variant) = node.attribute("variant") {
+        style.variant = Some(SynthesisVariant::from_str(variant, true).map_err(|_| error(node, "variant must be northern or southern"))?);
+    }
+    if let Some(strength) = node.attribute("strength") {
+        style.strength = Some(parse_strength(strength).ok_or_else(|| error(node, "strength must be between 0 and 1"))?);
+    }
+    Ok(style)
+}
+
+fn visit(node: roxmltree::Node, style: &SsmlStyle, document: &mut SsmlDocument) -> Result<(), SsmlError> {
+    if node.is_text() {
+        document.push_text(node.text().unwrap_or_default(), style);
+        return Ok(());
+    }
+    if !node.is_element() {
+        return Ok(());
+    }
+
+    let name = node.tag_name().name();
+    if node.tag_name().namespace() == Some(VENDOR_NAMESPACE) {
+        return match name {
+            "dialect" => visit_children(node, &dialect(&node, style)?, document),
+            _ => Err(error(&node, "unknown vendor element")),
+        };
+    }
+
+    match name {
+        "speak" | "p" | "s" | "voice" | "lang" | "mark" => visit_children(node, style, document),
+        "break" => {
+            document.items.push(SsmlItem::Break(break_time(&node)?));
+            Ok(())
+        },
+        "prosody" => {
+            let style = SsmlStyle { prosody: style.prosody.then(&prosody(&node)?), ..*style };
+            visit_children(node, &style, document)
+        },
+        "emphasis" => {
+            let style = SsmlStyle { prosody: style.prosody.then(&emphasis(&node)?), ..*style };
+            visit_children(node, &style, document)
+        },
+        "sub" => {
+            let alias = node.attribute("alias").ok_or_else(|| error(&node, "missing alias"))?;
+            document.push_text(alias, style);
+            Ok(())
+        },
+        "say-as" => {
+            document.push_text(&say_as(&node, &text_content(node))?, style);
+            Ok(())
+        },
+        "phoneme" => {
+            let ph = node.attribute("ph").ok_or_else(|| error(&node, "missing ph"))?;
+            match node.attribute("alphabet").unwrap_or("x-kana") {
+                "x-kana" | "kana" => {
+                    document.items.push(SsmlItem::Kana { kana: ph.trim().to_string(), style: *style });
+                },
+                alphabet => {
+                    log::warn!("Unsupported phoneme alphabet {}, reading the text instead", alphabet);
+                    visit_children(node, style, document)?;
+                },
+            }
+            Ok(())
+        },
+        "audio" => {
+            let src = node.attribute("src").ok_or_else(|| error(&node, "missing src"))?;
+            if src.contains("://") && !src.starts_with("file://") {
+                return Err(error(&node, "only local files can be played"));
+            }
+            document.items.push(SsmlItem::Audio {
+                src: PathBuf::from(src.trim_start_matches("file://")),
+                fallback: text_content(node).split_whitespace().collect(),
+                style: *style,
+            });
+            Ok(())
+        },
+        _ => {
+            log::warn!("Unsupported SSML element <{}>, reading its content", name);
+            visit_children(node, style, document)
+        },
+    }
+}
+
+fn visit_children(node: roxmltree::Node, style: &SsmlStyle, document: &mut SsmlDocument) -> Result<(), SsmlError> {
+    node.children().try_for_each(|child| visit(child, style, document))
+}
+
+fn text_content(node: roxmltree::Node) -> String {
+    node.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect()
+}
+
+/// Parses an SSML document. A root element other than `<speak>` is accepted
+/// and read as `<speak>`.
+pub fn parse(ssml: &str) -> Result<SsmlDocument, SsmlError> {
+    let xml = roxmltree::Document::parse(ssml).map_err(|e| SsmlError::new(e.to_string()))?;
+    let mut document = SsmlDocument::default();
+    visit_children(xml.root_element(), &SsmlStyle::default(), &mut document)?;
+    Ok(document.tidy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(document: &SsmlDocument) -> Vec<String> {
+        document.items.iter().map(|item| match item {
+            SsmlItem::Text { text, .. } => text.clone(),
+            SsmlItem::Kana { kana, .. } => format!("<{}>", kana),
+            SsmlItem::Break(time) => format!("|{}|", time),
+            SsmlItem::Audio { src, fallback, .. } => format!("[{} {}]", src.display(), fallback),
+        }).collect()
+    }
+
+    #[test]
+    fn parses_flat_items() {
+        let document = parse(r#"<speak>
+            こんにちは。<break time="500ms"/>
+            <sub alias="とうほく">東北</sub>の<say-as interpret-as="cardinal">1200</say-as>人に
+            <phoneme alphabet="x-kana" ph="ア'キタ">秋田</phoneme>
+            <audio src="chime.wav">チャイム</audio>
+        </speak>"#).unwrap();
+        assert_eq!(texts(&document), ["こんにちは。", "|0.5|", "とうほくの千二百人に", "<ア'キタ>", "[chime.wav チャイム]"]);
+    }
+
+    #[test]
+    fn nests_styles() {
+        let document = parse(&format!(r#"<speak xmlns:tohoku="{}">
+            <prosody rate="x-fast" pitch="+2st"><prosody rate="50%" volume="-6dB">速い</prosody></prosody>
+            <tohoku:dialect variant="southern" strength="50%">南</tohoku:dialect>
+        </speak>"#, VENDOR_NAMESPACE)).unwrap();
+        let SsmlItem::Text { style, .. } = &document.items[0] else { panic!() };
+        assert_eq!(style.prosody, Prosody { rate: 0.75, pitch: 2.0, volume: -6.0, intonation: 1.0 });
+        let SsmlItem::Text { style, .. } = &document.items[1] else { panic!() };
+        assert_eq!(style.variant, Some(SynthesisVariant::Southern));
+        assert_eq!(style.strength, Some(0.5));
+    }
+
+    #[test]
+    fn reports_errors_with_position() {
+        let err = parse("<speak>\n<break time=\"soon\"/></speak>").unwrap_err();
+        assert_eq!(err.to_string(), "2:1: <break>: invalid time (use s or ms)");
+        assert!(parse("<speak><tohoku:dialect/></speak>").is_err());
+        assert!(parse(r#"<speak><audio src="https://example.com/a.wav"/></speak>"#).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use crate::types::{self, Consonant, Vowel};
+
+/// Phonemes, lengths and pitch of one mora at one point of the pipeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoraState {
+    pub text: String,
+    pub consonant: Option<Consonant>,
+    pub vowel: Vowel,
+    pub consonant_length: Option<f64>,
+    pub vowel_length: f64,
+    pub pitch: f64,
+}
+
+impl From<&types::Mora> for MoraState {
+    fn from(mora: &types::Mora) -> Self {
+        Self {
+            text: mora.text.clone(),
+            consonant: mora.consonant,
+            vowel: mora.vowel,
+            consonant_length: mora.consonant_length,
+            vowel_length: mora.vowel_length,
+            pitch: mora.pitch,
+        }
+    }
+}
+
+/// What the dialect rules did to one mora.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoraTrace {
+    /// Index of the accent phrase in the transformed query
+    pub phrase: usize,
+
+    /// `None` for moras inserted by a rule
+    pub original: Option<MoraState>,
+
+    /// `None` for moras removed by a rule
+    pub transformed: Option<MoraState>,
+
+    /// Names of the rules that fired on this mora, in order
+    pub rules: Vec<String>,
+}
+
+/// Per-mora record of a dialect transform of one `AudioQuery`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryTrace {
+    pub moras: Vec<MoraTrace>,
+}
+
+/// Trace of one sentence of a synthesis request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentenceTrace {
+    pub text: String,
+    pub trace: QueryTrace,
+}
+
+fn fmt_phonemes(state: &MoraState) -> String {
+    match state.consonant {
+        Some(consonant) => format!("{} {}", consonant, state.vowel),
+        None => state.vowel.to_string(),
+    }
+}
+
+fn fmt_change<T: PartialEq>(original: Option<&MoraState>, transformed: Option<&MoraState>, f: impl Fn(&MoraState) -> T, show: impl Fn(&T) -> String) -> String {
+    match (original.map(&f), transformed.map(&f)) {
+        (Some(a), Some(b)) if a == b => show(&a),
+        (Some(a), Some(b)) => format!("{}→{}", show(&a), show(&b)),
+        (Some(a), None) => format!("{}→-", show(&a)),
+        (None, Some(b)) => format!("-→{}", show(&b)),
+        (None, None) => "-".to_string(),
+    }
+}
+
+impl fmt::Display for QueryTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "PHRASE\tTEXT\tPHONEMES\tPITCH\tVOWEL_LEN\tCONSONANT_LEN\tRULES")?;
+        for mora in &self.moras {
+            let original = mora.original.as_ref();
+            let transformed = mora.transformed.as_ref();
+            writeln!(f, "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                mora.phrase,
+                fmt_change(original, transformed, |m| m.text.clone(), |t| t.clone()),
+                fmt_change(original, transformed, fmt_phonemes, |t| t.clone()),
+                fmt_change(original, transformed, |m| m.pitch, |p| format!("{:.3}", p)),
+                fmt_change(original, transformed, |m| m.vowel_length, |l| format!("{:.3}", l)),
+                fmt_change(original, transformed, |m| m.consonant_length, |l| l.map_or("-".to_string(), |l| format!("{:.3}", l))),
+                mora.rules.join(","),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Follows moras through the dialect rules while they are inserted and removed.
+///
+/// A disabled tracer ignores every call, so the rules can report
+/// unconditionally.
+#[derive(Debug, Default)]
+pub(crate) struct Tracer {
+    enabled: bool,
+    entries: Vec<MoraTrace>,
+    /// Entry index of each live mora, per accent phrase
+    positions: Vec<Vec<usize>>,
+    /// Removed entries, keyed by the entry that preceded them
+    removed: Vec<(Option<usize>, usize)>,
+}
+
+impl Tracer {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn new(query: &types::AudioQuery) -> Self {
+        let mut entries = Vec::new();
+        let positions = query.accent_phrases.iter().enumerate().map(|(i, phrase)| {
+            phrase.moras.iter().map(|mora| {
+                entries.push(MoraTrace {
+                    phrase: i,
+                    original: Some(mora.into()),
+                    transformed: None,
+                    rules: Vec::new(),
+                });
+                entries.len() - 1
+            }).collect()
+        }).collect();
+        Self {
+            enabled: true,
+            entries,
+            positions,
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn fire(&mut self, phrase: usize, mora: usize, rule: &str) {
+        if !self.enabled {
+            return;
+        }
+        if let Some(&entry) = self.positions.get(phrase).and_then(|p| p.get(mora)) {
+            self.entries[entry].rules.push(rule.to_string());
+        }
+    }
+
+    pub fn fire_phrase(&mut self, phrase: usize, rule: &str) {
+        let len = self.positions.get(phrase).map_or(0, |p| p.len());
+        for mora in 0..len {
+            self.fire(phrase, mora, rule);
+        }
+    }
+
+    pub fn insert(&mut self, phrase: usize, mora: usize, rule: &str) {
+        if !self.enabled {
+            return;
+        }
+        self.entries.push(MoraTrace {
+            phrase,
+            original: None,
+            transformed: None,
+            rules: vec![rule.to_string()],
+        });
+        let entry = self.entries.len() - 1;
+        if let Some(positions) = self.positions.get_mut(phrase) {
+            positions.insert(mora.min(positions.len()), entry);
+        }
+    }
+
+    pub fn remove(&mut self, phrase: usize, mora: usize, rule: &str) {
+        self.fire(phrase, mora, rule);
+        let Some(positions) = self.positions.get_mut(phrase) else {
+            return;
+        };
+        if mora < positions.len() {
+            let entry = positions.remove(mora);
+            let anchor = mora.checked_sub(1).map(|prev| positions[prev]);
+            self.removed.push((anchor, entry));
+        }
+    }
+
+    pub fn remove_phrase(&mut self, phrase: usize) {
+        if phrase < self.positions.len() {
+            self.positions.remove(phrase);
+        }
+    }
+
+    /// Attaches the final state of every live mora and returns the trace in mora order.
+    pub fn finish(mut self, query: &types::AudioQuery) -> QueryTrace {
+        if !self.enabled {
+            return QueryTrace::default();
+        }
+        let mut order = Vec::with_capacity(self.entries.len());
+        for (i, positions) in self.positions.iter().enumerate() {
+            for (j, &entry) in positions.iter().enumerate() {
+                self.entries[entry].phrase = i;
+                self.entries[entry].transformed = query.accent_phrases.get(i)
+                    .and_then(|phrase| phrase.moras.get(j))
+                    .map(|mora| mora.into());
+                order.push(entry);
+                order.extend(self.removed.iter().filter(|(anchor, _)| *anchor == Some(entry)).map(|(_, removed)| *removed));
+            }
+        }
+        let moras = order.into_iter().map(|entry| self.entries[entry].clone()).collect();
+        QueryTrace {
+            moras,
+        }
+    }
+}
@@ -1,6 +1,11 @@
 
 use serde::{Deserialize, Serialize};
 
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::GenericError;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AudioQuery {
     pub accent_phrases: Vec<AccentPhrase>,
@@ -26,10 +31,10 @@ pub struct AccentPhrase {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Mora {
     pub text: String,
-    pub vowel: String,
+    pub vowel: Vowel,
     pub vowel_length: f64,
     pub pitch: f64,
-    pub consonant: Option<String>,
+    pub consonant: Option<Consonant>,
     pub consonant_length: Option<f64>,
 }
 
@@ -47,3 +52,254 @@ pub struct Speaker {
     pub speaker_uuid: String,
     pub version: String,
 }
+
+/// Declares a phoneme enum whose variants serialize to their Open JTalk symbols.
+macro_rules! phonemes {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$vmeta:meta])* $variant:ident => $symbol:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum $name {
+            $($(#[$vmeta])* #[serde(rename = $symbol)] $variant,)*
+        }
+
+        impl $name {
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)*];
+
+            /// Open JTalk symbol
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $symbol,)*
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = GenericError<&'static str>;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($symbol => Ok(Self::$variant),)*
+                    _ => Err(GenericError::new(concat!("Unknown ", stringify!($name)))),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+phonemes! {
+    /// Vowel (or mora-level) phoneme of a `Mora`, including devoiced vowels,
+    /// the moraic nasal, the geminate and pauses.
+    pub enum Vowel {
+        A => "a",
+        I => "i",
+        U => "u",
+        E => "e",
+        O => "o",
+        UnvoicedA => "A",
+        UnvoicedI => "I",
+        UnvoicedU => "U",
+        UnvoicedE => "E",
+        UnvoicedO => "O",
+        /// Moraic nasal (ン)
+        N => "N",
+        /// Geminate (ッ)
+        Cl => "cl",
+        /// Pause
+        Pau => "pau",
+    }
+}
+
+impl Vowel {
+    /// One of the five vowels, voiced or devoiced
+    pub fn is_vowel(&self) -> bool {
+        !self.is_special()
+    }
+
+    /// `N`, `cl` and `pau`
+    pub fn is_special(&self) -> bool {
+        matches!(self, Self::N | Self::Cl | Self::Pau)
+    }
+
+    pub fn is_devoiced(&self) -> bool {
+        matches!(self, Self::UnvoicedA | Self::UnvoicedI | Self::UnvoicedU | Self::UnvoicedE | Self::UnvoicedO)
+    }
+
+    /// Devoiced form of a vowel; special phonemes are returned unchanged.
+    pub fn devoiced(self) -> Self {
+        match self {
+            Self::A => Self::UnvoicedA,
+            Self::I => Self::UnvoicedI,
+            Self::U => Self::UnvoicedU,
+            Self::E => Self::UnvoicedE,
+            Self::O => Self::UnvoicedO,
+            other => other,
+        }
+    }
+
+    /// Voiced form of a vowel; special phonemes are returned unchanged.
+    pub fn voiced(self) -> Self {
+        match self {
+            Self::UnvoicedA => Self::A,
+            Self::UnvoicedI => Self::I,
+            Self::UnvoicedU => Self::U,
+            Self::UnvoicedE => Self::E,
+            Self::UnvoicedO => Self::O,
+            other => other,
+        }
+    }
+}
+
+phonemes! {
+    /// Consonant phoneme of a `Mora`, in Open JTalk notation.
+    pub enum Consonant {
+        B => "b",
+        By => "by",
+        Ch => "ch",
+        D => "d",
+        Dy => "dy",
+        F => "f",
+        G => "g",
+        Gw => "gw",
+        Gy => "gy",
+        H => "h",
+        Hy => "hy",
+        J => "j",
+        K => "k",
+        Kw => "kw",
+        Ky => "ky",
+        M => "m",
+        My => "my",
+        N => "n",
+        Ny => "ny",
+        P => "p",
+        Py => "py",
+        R => "r",
+        Ry => "ry",
+        S => "s",
+        Sh => "sh",
+        T => "t",
+        Ts => "ts",
+        Ty => "ty",
+        V => "v",
+        W => "w",
+        Y => "y",
+        Z => "z",
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Place {
+    Bilabial,
+    Labiodental,
+    Alveolar,
+    /// Alveolo-palatal (シ, チ, ジ)
+    Postalveolar,
+    Palatal,
+    Velar,
+    Glottal,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Manner {
+    Stop,
+    Affricate,
+    Fricative,
+    Nasal,
+    Flap,
+    Approximant,
+}
+
+impl Consonant {
+    pub fn is_voiced(&self) -> bool {
+        matches!(self,
+            Self::B | Self::By | Self::D | Self::Dy | Self::G | Self::Gw | Self::Gy | Self::J
+            | Self::M | Self::My | Self::N | Self::Ny | Self::R | Self::Ry | Self::V | Self::W
+            | Self::Y | Self::Z)
+    }
+
+    /// Palatalized (拗音) consonants such as `ky`, `sh` or `ch`
+    pub fn is_palatalized(&self) -> bool {
+        matches!(self,
+            Self::By | Self::Dy | Self::Gy | Self::Hy | Self::Ky | Self::My | Self::Ny | Self::Py
+            | Self::Ry | Self::Ty | Self::Sh | Self::Ch | Self::J)
+    }
+
+    /// Voiced counterpart (濁音) of a voiceless consonant; voiced consonants are returned unchanged.
+    pub fn voiced(self) -> Self {
+        match self {
+            Self::K => Self::G,
+            Self::Kw => Self::Gw,
+            Self::Ky => Self::Gy,
+            Self::S | Self::Ts => Self::Z,
+            Self::Sh | Self::Ch => Self::J,
+            Self::T => Self::D,
+            Self::Ty => Self::Dy,
+            Self::H | Self::F | Self::P => Self::B,
+            Self::Hy | Self::Py => Self::By,
+            other => other,
+        }
+    }
+
+    pub fn place(&self) -> Place {
+        match self {
+            Self::B | Self::By | Self::P | Self::Py | Self::M | Self::My | Self::F => Place::Bilabial,
+            Self::V => Place::Labiodental,
+            Self::D | Self::Dy | Self::T | Self::Ty | Self::S | Self::Z | Self::Ts | Self::N | Self::R | Self::Ry => Place::Alveolar,
+            Self::Sh | Self::Ch | Self::J => Place::Postalveolar,
+            Self::Ny | Self::Hy | Self::Y => Place::Palatal,
+            Self::K | Self::Kw | Self::Ky | Self::G | Self::Gw | Self::Gy | Self::W => Place::Velar,
+            Self::H => Place::Glottal,
+        }
+    }
+
+    pub fn manner(&self) -> Manner {
+        match self {
+            Self::B | Self::By | Self::D | Self::Dy | Self::G | Self::Gw | Self::Gy
+            | Self::K | Self::Kw | Self::Ky | Self::P | Self::Py | Self::T | Self::Ty => Manner::Stop,
+            Self::Ch | Self::Ts | Self::J => Manner::Affricate,
+            Self::F | Self::H | Self::Hy | Self::S | Self::Sh | Self::V | Self::Z => Manner::Fricative,
+            Self::M | Self::My | Self::N | Self::Ny => Manner::Nasal,
+            Self::R | Self::Ry => Manner::Flap,
+            Self::W | Self::Y => Manner::Approximant,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phonemes_round_trip_voicevox_json() {
+        let json = r#"{"text":"シ","vowel":"I","vowel_length":0.05,"pitch":0.0,"consonant":"sh","consonant_length":0.07}"#;
+        let mora: Mora = serde_json::from_str(json).unwrap();
+        assert_eq!(mora.vowel, Vowel::UnvoicedI);
+        assert_eq!(mora.consonant, Some(Consonant::Sh));
+        assert_eq!(serde_json::to_string(&mora).unwrap(), json);
+
+        for vowel in Vowel::ALL {
+            assert_eq!(vowel.as_str().parse::<Vowel>().unwrap(), *vowel);
+        }
+        for consonant in Consonant::ALL {
+            assert_eq!(consonant.as_str().parse::<Consonant>().unwrap(), *consonant);
+        }
+    }
+
+    #[test]
+    fn unknown_phonemes_are_rejected() {
+        let json = r#"{"text":"ア","vowel":"x","vowel_length":0.1,"pitch":5.0,"consonant":null,"consonant_length":null}"#;
+        assert!(serde_json::from_str::<Mora>(json).is_err());
+        assert!("kk".parse::<Consonant>().is_err());
+    }
+}
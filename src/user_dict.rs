@@ -0,0 +1,308 @@
+//! User pronunciation dictionary.
+//!
+//! VOICEVOX Core 0.14 has no user dictionary of its own, so words are
+//! matched here and read through the kana (AquesTalk notation) input of
+//! `audio_query`, with the rest of the sentence analysed as usual.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::GenericError;
+
+pub type UserDictError = GenericError<&'static str>;
+
+/// One word of the user dictionary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserWord {
+    /// Text as written in the input
+    pub surface: String,
+
+    /// Reading in katakana
+    pub reading: String,
+
+    /// Mora after which the pitch falls; 0 for an unaccented word
+    #[serde(default)]
+    pub accent: usize,
+
+    /// Words with a higher priority win over overlapping matches
+    #[serde(default = "UserWord::default_priority")]
+    pub priority: u8,
+}
+
+impl UserWord {
+    pub const PRIORITY_MAX: u8 = 10;
+
+    fn default_priority() -> u8 {
+        5
+    }
+
+    pub fn new(surface: &str, reading: &str, accent: usize, priority: u8) -> Result<Self, UserDictError> {
+        let word = Self {
+            surface: surface.to_string(),
+            reading: reading.to_string(),
+            accent,
+            priority,
+        };
+        word.validate()?;
+        Ok(word)
+    }
+
+    pub fn validate(&self) -> Result<(), UserDictError> {
+        if self.surface.is_empty() {
+            return Err(UserDictError::new("Surface must not be empty"));
+        }
+        let mut chars = self.reading.chars();
+        match chars.next() {
+            None => return Err(UserDictError::new("Reading must not be empty")),
+            Some(c) if is_small_kana(c) || c == 'ー' => return Err(UserDictError::new("Reading must not start with a small kana or ー")),
+            _ => {},
+        }
+        if !self.reading.chars().all(|c| matches!(c, 'ァ'..='ヴ' | 'ー')) {
+            return Err(UserDictError::new("Reading must be written in katakana"));
+        }
+        if self.accent > self.mora_count() {
+            return Err(UserDictError::new("Accent is beyond the last mora"));
+        }
+        if self.priority > Self::PRIORITY_MAX {
+            return Err(UserDictError::new("Priority must be between 0 and 10"));
+        }
+        Ok(())
+    }
+
+    /// Number of moras of the reading (small ャ/ュ/ョ, ァ/ィ/… join the previous mora)
+    pub fn mora_count(&self) -> usize {
+        self.reading.chars().filter(|c| !is_small_kana(*c)).count()
+    }
+
+    /// Reading in the kana notation of `audio_query`, as one accent phrase.
+    pub fn kana(&self) -> String {
+        // unaccented phrases carry the mark after their last mora
+        let accent = if self.accent == 0 { self.mora_count() } else { self.accent };
+        let mut kana = String::new();
+        let mut moras = 0;
+        let mut chars = self.reading.chars().peekable();
+        while let Some(c) = chars.next() {
+            kana.push(c);
+            if !is_small_kana(c) {
+                moras += 1;
+            }
+            let mora_ends = chars.peek().is_none_or(|next| !is_small_kana(*next));
+            if mora_ends && moras == accent {
+                kana.push('\'');
+            }
+        }
+        kana
+    }
+}
+
+/// Tohoku place names, one `surface<TAB>reading<TAB>accent` per line
+const PLACE_NAMES: &str = include_str!("../data/place_names.tsv");
+
+/// Priority of the bundled place names, below the default of user words
+pub const PLACE_NAME_PRIORITY: u8 = 3;
+
+fn parse_place_name(line: &str) -> Result<UserWord, UserDictError> {
+    let mut fields = line.split('\t');
+    let (Some(surface), Some(reading)) = (fields.next(), fields.next()) else {
+        return Err(UserDictError::new("Expected surface, reading and accent separated by tabs"));
+    };
+    let accent = match fields.next() {
+        Some(accent) => accent.trim().parse().map_err(|_| UserDictError::new("Accent must be a number"))?,
+        None => 0,
+    };
+    UserWord::new(surface.trim(), reading.trim(), accent, PLACE_NAME_PRIORITY)
+}
+
+/// The bundled lexicon of Tohoku municipality, district, station and
+/// landmark names, as a dictionary to merge user words into.
+pub fn place_names() -> UserDict {
+    let mut dict = UserDict::new();
+    for (number, line) in PLACE_NAMES.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let word = match parse_place_name(line) {
+            Ok(word) => word,
+            Err(e) => {
+                log::warn!("place_names.tsv:{}: {}", number + 1, e);
+                continue;
+            },
+        };
+        // both ways of writing the small ke
+        for variant in [word.surface.replace('ヶ', "ケ"), word.surface.replace('ケ', "ヶ")] {
+            if variant != word.surface {
+                let _ = dict.add(UserWord { surface: variant, ..word.clone() });
+            }
+        }
+        let _ = dict.add(word);
+    }
+    dict
+}
+
+fn is_small_kana(c: char) -> bool {
+    "ァィゥェォャュョヮ".contains(c)
+}
+
+/// A piece of text, either left to the usual analysis or read from the dictionary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Word(&'a UserWord),
+}
+
+/// Words with custom readings, matched against the text after
+/// normalization and lexical conversion.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserDict {
+    words: Vec<UserWord>,
+}
+
+impl UserDict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn words(&self) -> &[UserWord] {
+        &self.words
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Adds a word, replacing (and returning) any word with the same surface.
+    pub fn add(&mut self, word: UserWord) -> Result<Option<UserWord>, UserDictError> {
+        word.validate()?;
+        match self.words.iter_mut().find(|w| w.surface == word.surface) {
+            Some(existing) => Ok(Some(std::mem::replace(existing, word))),
+            None => {
+                self.words.push(word);
+                Ok(None)
+            },
+        }
+    }
+
+    pub fn remove(&mut self, surface: &str) -> Option<UserWord> {
+        let index = self.words.iter().position(|w| w.surface == surface)?;
+        Some(self.words.remove(index))
+    }
+
+    /// Adds every word of `other`; its words replace ours on the same surface.
+    pub fn merge(&mut self, other: UserDict) -> Result<(), UserDictError> {
+        for word in other.words {
+            self.add(word)?;
+        }
+        Ok(())
+    }
+
+    /// Checks every word, e.g. after loading a hand-edited file.
+    pub fn validate(&self) -> Result<(), UserDictError> {
+        self.words.iter().try_for_each(UserWord::validate)
+    }
+
+    /// Splits `text` into dictionary words and the text between them.
+    ///
+    /// Text is scanned from the start; at each position the matching word
+    /// with the highest priority wins, then the longest.
+    pub fn segments<'a>(&'a self, text: &'a str) -> Vec<Segment<'a>> {
+        let mut segments = Vec::new();
+        let mut plain_start = 0;
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            let word = self.words.iter()
+                .filter(|w| rest.starts_with(w.surface.as_str()))
+                .max_by_key(|w| (w.priority, w.surface.len()));
+            match word {
+                Some(word) => {
+                    if plain_start < i {
+                        segments.push(Segment::Text(&text[plain_start..i]));
+                    }
+                    segments.push(Segment::Word(word));
+                    i += word.surface.len();
+                    plain_start = i;
+                },
+                None => i += rest.chars().next().map_or(1, char::len_utf8),
+            }
+        }
+        if plain_start < text.len() {
+            segments.push(Segment::Text(&text[plain_start..]));
+        }
+        segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_kana() {
+        assert_eq!(UserWord::new("東北", "トーホク", 0, 5).unwrap().kana(), "トーホク'");
+        assert_eq!(UserWord::new("仙台", "センダイ", 1, 5).unwrap().kana(), "セ'ンダイ");
+        assert_eq!(UserWord::new("東京", "トーキョー", 3, 5).unwrap().kana(), "トーキョ'ー");
+        assert_eq!(UserWord::new("京", "キョウ", 1, 5).unwrap().kana(), "キョ'ウ");
+    }
+
+    #[test]
+    fn rejects_invalid_words() {
+        assert!(UserWord::new("", "ア", 0, 5).is_err());
+        assert!(UserWord::new("亜", "あ", 0, 5).is_err());
+        assert!(UserWord::new("亜", "ャア", 0, 5).is_err());
+        assert!(UserWord::new("亜", "キャ", 2, 5).is_err());
+        assert!(UserWord::new("亜", "ア", 0, 11).is_err());
+    }
+
+    fn readings(dict: &UserDict, text: &str) -> Vec<String> {
+        dict.segments(text).iter().map(|s| match s {
+            Segment::Text(t) => t.to_string(),
+            Segment::Word(w) => format!("[{}]", w.reading),
+        }).collect()
+    }
+
+    #[test]
+    fn segments_by_priority_then_length() {
+        let mut dict = UserDict::new();
+        dict.add(UserWord::new("岩手", "イワテ", 0, 5).unwrap()).unwrap();
+        dict.add(UserWord::new("岩手山", "イワテサン", 1, 5).unwrap()).unwrap();
+
+        assert_eq!(readings(&dict, "岩手山に登る"), ["[イワテサン]", "に登る"]);
+        assert_eq!(readings(&dict, "ここは岩手"), ["ここは", "[イワテ]"]);
+
+        dict.add(UserWord::new("岩手", "イワテ", 0, 9).unwrap()).unwrap();
+        assert_eq!(readings(&dict, "岩手山に登る"), ["[イワテ]", "山に登る"]);
+    }
+
+    #[test]
+    fn place_names_are_valid() {
+        for (number, line) in PLACE_NAMES.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(e) = parse_place_name(line) {
+                panic!("place_names.tsv:{}: {}: {}", number + 1, e, line);
+            }
+        }
+
+        let dict = place_names();
+        assert_eq!(readings(&dict, "左沢駅から閖上へ"), ["[アテラザワ]", "駅から", "[ユリアゲ]", "へ"]);
+        assert_eq!(readings(&dict, "金ケ崎"), ["[カネガサキ]"]);
+        assert_eq!(readings(&dict, "陸前高田"), ["[リクゼンタカタ]"]);
+        // words that are also common nouns are only registered as compounds
+        assert_eq!(readings(&dict, "台風が北上する"), ["台風が北上する"]);
+        assert_eq!(readings(&dict, "最上階"), ["最上階"]);
+        assert_eq!(readings(&dict, "大間違い"), ["大間違い"]);
+        assert_eq!(readings(&dict, "東通りの住宅三戸"), ["東通りの住宅三戸"]);
+        assert_eq!(readings(&dict, "北上川と最上川"), ["[キタカミガワ]", "と", "[モガミガワ]"]);
+    }
+
+    #[test]
+    fn add_replaces_same_surface() {
+        let mut dict = UserDict::new();
+        assert!(dict.add(UserWord::new("遠野", "トーノ", 1, 5).unwrap()).unwrap().is_none());
+        let old = dict.add(UserWord::new("遠野", "トオノ", 1, 5).unwrap()).unwrap();
+        assert_eq!(old.unwrap().reading, "トーノ");
+        assert_eq!(dict.words().len(), 1);
+        assert!(dict.remove("遠野").is_some());
+        assert!(dict.is_empty());
+    }
+}
@@ -5,24 +5,34 @@ use vvcore::*;
 use std::collections::BTreeMap;
 use std::sync::OnceLock;
 use std::ffi::CString;
-use std::io::Cursor;
 
 use tokio::sync::mpsc;
 use tokio::sync::oneshot;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fmt::Debug;
 
 use clap::ValueEnum;
-use hound::{WavReader, WavWriter};
 
-use crate::types;
+use crate::types::{self, Consonant};
 use crate::error::*;
 use crate::EngineErrorDescription;
 use crate::EngineError;
-use crate::TextSplitter;
+use crate::{PartKind, TextSplitter};
+use crate::dialect::{self, DialectProfile, QuestionContours, QuoteStyle, SpeakerPitch};
+use crate::kana;
+use crate::lexical;
+use crate::normalize::{self, NormalizeOptions};
+use crate::trace::{QueryTrace, SentenceTrace, Tracer};
+use crate::user_dict::{self, Segment, UserDict};
+use crate::ssml::{Prosody, SsmlDocument, SsmlItem, SsmlStyle};
+use crate::script::{LineTiming, Manifest, Script};
+use crate::audio;
 
 static ENGINE: OnceLock<EngineHandle> = OnceLock::new();
 
+/// Sentence used to measure the pitch baseline of each speaker.
+const CALIBRATION_TEXT: &str = "これは、声の高さをはかるための、ごく普通の文章です。";
+
 type InternalError = GenericError<&'static str>;
 
 struct EngineRequestData<Req, Res> {
@@ -61,7 +71,26 @@ where
 pub struct SynthesisOptions {
     pub variant: SynthesisVariant,
     pub params: SynthesisParams,
+    pub profile: DialectProfile,
     pub speaker_id: u32,
+
+    /// Sentences longer than this (in characters) are split at clause
+    /// boundaries; `None` sends every sentence whole
+    pub max_sentence_length: Option<usize>,
+
+    /// Rewriting of numbers, dates, units, … applied before anything else
+    pub normalization: NormalizeOptions,
+
+    /// Replaces the engine's user dictionary for this request
+    pub user_dict: Option<UserDict>,
+
+    /// How much of the dialect prosody to apply, from 0.0 (standard
+    /// Japanese) to 1.0. Text rewriting and sound changes are not scaled.
+    pub dialect_strength: f64,
+
+    /// Directory SSML `<audio>` files are read from; `src` must be a
+    /// relative path inside it. `None` reads the fallback text instead.
+    pub audio_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -137,19 +166,78 @@ pub enum SynthesisVariant {
 }
 
 impl SynthesisVariant {
-    fn make_voiced_consonant(&self, consonant: &str) -> Option<String> {
+    pub fn default_profile(&self) -> DialectProfile {
+        match self {
+            Self::Northern => DialectProfile::northern(),
+            Self::Southern => DialectProfile::southern(),
+        }
+    }
+
+    fn default_question_contours(&self) -> QuestionContours {
+        match self {
+            Self::Northern => QuestionContours::northern(),
+            Self::Southern => QuestionContours::southern(),
+        }
+    }
+
+    fn make_voiced_consonant(&self, consonant: Consonant) -> Consonant {
         match consonant {
-            "k" => Some("g".to_string()),
-            "s" => Some("z".to_string()),
-            "t" => Some("d".to_string()),
-            _ => Some(consonant.to_string()),
+            Consonant::K | Consonant::S | Consonant::T => consonant.voiced(),
+            _ => consonant,
         }
     }
-    fn preprocess_audio_query(&self, query: types::AudioQuery, params: SynthesisParams) -> types::AudioQuery {
-        let unvoiced_vowels = vec!["A", "I", "U", "E", "O"];
-        let voiced_consonants = vec!["g", "z", "d", "b", "n"];
+    /// Applies the dialect rules of this variant to a query returned by `audio_query`.
+    pub fn apply_dialect(&self, query: types::AudioQuery, params: SynthesisParams, profile: &DialectProfile, pitch: &SpeakerPitch) -> types::AudioQuery {
+        self.preprocess_audio_query(query, None, params, profile, pitch, &mut Tracer::disabled())
+    }
+
+    /// Same as `apply_dialect` for a query made from `text`, which lets the
+    /// final particles be told apart from words that end the same (魚, 鍋).
+    pub fn apply_dialect_with_text(&self, query: types::AudioQuery, text: &str, params: SynthesisParams, profile: &DialectProfile, pitch: &SpeakerPitch) -> types::AudioQuery {
+        self.preprocess_audio_query(query, Some(text), params, profile, pitch, &mut Tracer::disabled())
+    }
+
+    /// Same as `apply_dialect`, also returning which rules changed each mora.
+    pub fn apply_dialect_traced(&self, query: types::AudioQuery, params: SynthesisParams, profile: &DialectProfile, pitch: &SpeakerPitch) -> (types::AudioQuery, QueryTrace) {
+        let mut tracer = Tracer::new(&query);
+        let query = self.preprocess_audio_query(query, None, params, profile, pitch, &mut tracer);
+        let trace = tracer.finish(&query);
+        (query, trace)
+    }
+
+    fn preprocess_audio_query(&self, query: types::AudioQuery, text: Option<&str>, params: SynthesisParams, profile: &DialectProfile, pitch: &SpeakerPitch, tracer: &mut Tracer) -> types::AudioQuery {
+        let voiced_consonants = [Consonant::G, Consonant::Z, Consonant::D, Consonant::B, Consonant::N];
 
         let mut query = query.clone();
+
+        dialect::remove_empty_phrases(&mut query.accent_phrases, tracer);
+        let levels = dialect::phrase_pitch_levels(&query.accent_phrases, pitch);
+        let questions = dialect::question_types(&query.accent_phrases, profile.questions.is_some());
+        let question_contours = profile.questions.unwrap_or_else(|| self.default_question_contours());
+        let particles = dialect::final_particles(&query.accent_phrases, &profile.final_particles, &questions, text);
+
+        for (i, accent_phrase) in query.accent_phrases.iter_mut().enumerate() {
+            if !accent_phrase.moras.iter().any(|mora| mora.pitch > 0.0) {
+                tracer.fire_phrase(i, "pitch_fallback");
+            }
+            if let Some(merger) = profile.central_vowel_merger {
+                dialect::apply_central_vowel_merger(accent_phrase, merger, i, tracer);
+            }
+            if let Some(coalescence) = &profile.vowel_coalescence {
+                dialect::apply_vowel_coalescence(accent_phrase, coalescence, i, tracer);
+            }
+        }
+
+        let declination = match &profile.declination {
+            Some(declination) => dialect::declination_factors(&query.accent_phrases, declination),
+            None => vec![1.0; query.accent_phrases.len()],
+        };
+        for (i, factor) in declination.iter().enumerate() {
+            if *factor != 1.0 {
+                tracer.fire_phrase(i, "declination");
+            }
+        }
+
         query.speed_scale = 1.1;
         query.pitch_scale = 0.0;
         query.intonation_scale = 0.8;
@@ -159,158 +247,177 @@ impl SynthesisVariant {
             Self::Northern => {
                 let mut accent_phrases = query.accent_phrases.clone();
                 for i in 0..accent_phrases.len() {
-                    let accent_phrases_len = accent_phrases.len();
+                    let last_accent_phrase = dialect::ends_intonation_unit(&accent_phrases, i);
                     let accent_phrase = &mut accent_phrases[i];
-                    assert!(accent_phrase.moras.len() > 0);
-                    let pitches = accent_phrase.moras.iter().map(|m| m.pitch).filter(|pitch| *pitch != 0.0).collect::<Vec<f64>>();
-                    assert!(pitches.len() > 0);
-                    let avg_pitch = pitches.iter().sum::<f64>() / pitches.len() as f64;
-                    let last_accent_phrase = match accent_phrase.clone().pause_mora {
-                        Some(mora) => mora.vowel == "pau" && mora.vowel_length >= 0.3,
-                        None => i == accent_phrases_len - 1,
-                    };
+                    let avg_pitch = levels[i];
                     
                     let avg_pitch = if last_accent_phrase {
-                        avg_pitch * 0.97
+                        pitch.scale(avg_pitch, 0.97)
                     } else {
                         avg_pitch
                     };
+                    let avg_pitch = pitch.scale(avg_pitch, declination[i]);
 
-                    let accent = (accent_phrase.accent - 1) as usize; // originally 1-indexed
+                    let peak = dialect::northern_accent_peak(accent_phrase, profile.northern_accent);
                     for j in 0..accent_phrase.moras.len() {
                         let mut mora = accent_phrase.moras[j].clone();
-                        if unvoiced_vowels.contains(&mora.vowel.as_str()) {
-                            mora.vowel = mora.vowel.to_lowercase();
+                        if mora.vowel.is_devoiced() {
+                            mora.vowel = mora.vowel.voiced();
                             mora.vowel_length *= 1.5;
                             if let Some(len) = &mora.consonant_length {
                                 mora.consonant_length = Some(len * 0.6);
                             }
+                            tracer.fire(i, j, "revoicing");
                         }
                         let next_mora = j + 1;
                         if next_mora < accent_phrase.moras.len() {
                             let next_mora = accent_phrase.moras[next_mora].clone();
-                            if let Some(consonant) = &next_mora.consonant {
-                                if voiced_consonants.contains(&consonant.as_str()) {
-                                   if let Some(consonant) = &mora.consonant {
-                                       mora.consonant = self.make_voiced_consonant(consonant);
+                            if let Some(consonant) = next_mora.consonant {
+                                if voiced_consonants.contains(&consonant) {
+                                   if let Some(consonant) = mora.consonant {
+                                       mora.consonant = Some(self.make_voiced_consonant(consonant));
+                                       if mora.consonant != Some(consonant) {
+                                           tracer.fire(i, j, "consonant_voicing");
+                                       }
                                    }
                                 }
                             }
-                            if voiced_consonants.contains(&next_mora.vowel.as_str()) {
-                                mora.vowel = mora.vowel.to_uppercase();
-                            }
                         }
-                        let last_mora = (accent_phrase.moras.len() - 1) == j;
+                        let last_mora = (accent_phrase.moras.len() - 1) == j && particles[i].is_none();
                         if last_mora {
                             mora.vowel_length *= 1.5;
+                            tracer.fire(i, j, "phrase_final_lengthening");
                         }
 
-                        if j < accent {
-                            mora.pitch = avg_pitch * 0.95;
-                        } else if j == accent {
-                            mora.pitch = avg_pitch * 1.07;
+                        if j < peak.start {
+                            mora.pitch = pitch.scale(avg_pitch, 0.95);
+                            tracer.fire(i, j, "pre_peak_lowering");
+                        } else if peak.contains(&j) {
+                            mora.pitch = pitch.scale(avg_pitch, 1.07);
+                            tracer.fire(i, j, "northern_peak");
                         } else {
                             mora.pitch = avg_pitch;
                         }
                         if last_accent_phrase && last_mora {
                             mora.vowel_length *= 1.25;
-                            if accent_phrase.is_interrogative {
-                                mora.pitch *= 1.02;
-                            } else {
-                                mora.pitch *= 0.96;
+                            if questions[i].is_none() {
+                                mora.pitch = pitch.scale(mora.pitch, 0.96);
+                                tracer.fire(i, j, "utterance_final");
                             }
                         }
                         accent_phrase.moras[j] = mora;
                     }
+
+                    if let Some((len, particle)) = particles[i] {
+                        dialect::apply_final_particle(accent_phrase, len, particle, pitch, i, tracer);
+                    } else if let Some(question) = questions[i] {
+                        dialect::apply_question_contour(accent_phrase, question, question_contours.get(question), pitch, i, tracer);
+                    }
+
+                    if let Some(prenasalization) = &profile.prenasalization {
+                        dialect::apply_prenasalization(accent_phrase, &query.accent_phrases[i], prenasalization, i, tracer);
+                    }
                 }
                 query.accent_phrases = accent_phrases;
-
-                params.apply(&mut query);
-                query
             },
             Self::Southern => {
                 query.pitch_scale = 0.01;
                 query.intonation_scale = 0.7;
 
+                let mut rng = profile.southern_contour.rng();
                 let mut accent_phrases = query.accent_phrases.clone();
                 for i in 0..accent_phrases.len() {
-                    let accent_phrases_len = accent_phrases.len();
+                    let last_accent_phrase = dialect::ends_intonation_unit(&accent_phrases, i);
                     let accent_phrase = &mut accent_phrases[i];
-                    assert!(accent_phrase.moras.len() > 0);
-                    let pitches = accent_phrase.moras.iter().map(|m| m.pitch).filter(|pitch| *pitch != 0.0).collect::<Vec<f64>>();
-                    assert!(pitches.len() > 0);
-                    let avg_pitch = pitches.iter().sum::<f64>() / pitches.len() as f64;
-                    let last_accent_phrase = match accent_phrase.clone().pause_mora {
-                        Some(mora) => mora.vowel == "pau" && mora.vowel_length >= 0.3,
-                        None => i == accent_phrases_len - 1,
-                    };
+                    let avg_pitch = levels[i];
 
                     let avg_pitch = if last_accent_phrase {
-                        avg_pitch * 0.97
+                        pitch.scale(avg_pitch, 0.97)
                     } else {
                         avg_pitch
                     };
+                    let avg_pitch = pitch.scale(avg_pitch, declination[i]);
+
+                    let contour = profile.southern_contour.factors(accent_phrase.moras.len(), &mut rng);
+                    for (mora, factor) in accent_phrase.moras.iter_mut().zip(contour) {
+                        mora.pitch = pitch.scale(avg_pitch, factor);
+                    }
+                    tracer.fire_phrase(i, "southern_contour");
 
-                    let accent = (accent_phrase.moras.len() - 1) as usize;
                     for j in 0..accent_phrase.moras.len() {
                         let mut mora = accent_phrase.moras[j].clone();
                         let next_mora = j + 1;
                         let mut next_unvoiced = false;
                         if next_mora < accent_phrase.moras.len() {
                             let next_mora = accent_phrase.moras[next_mora].clone();
-                            if let Some(consonant) = &next_mora.consonant {
-                                if voiced_consonants.contains(&consonant.as_str()) {
-                                   if let Some(consonant) = &mora.consonant {
-                                       mora.consonant = self.make_voiced_consonant(consonant);
+                            if let Some(consonant) = next_mora.consonant {
+                                if voiced_consonants.contains(&consonant) {
+                                   if let Some(consonant) = mora.consonant {
+                                       mora.consonant = Some(self.make_voiced_consonant(consonant));
+                                       if mora.consonant != Some(consonant) {
+                                           tracer.fire(i, j, "consonant_voicing");
+                                       }
                                    }
                                 }
                             }
-                            if unvoiced_vowels.contains(&next_mora.vowel.as_str()) {
+                            if next_mora.vowel.is_devoiced() {
                                 next_unvoiced = true;
                             }
-                            if voiced_consonants.contains(&next_mora.vowel.as_str()) {
-                                mora.vowel = mora.vowel.to_uppercase();
-                            }
                         }
 
-                        let last_mora = j == (accent_phrase.moras.len() - 1) || next_unvoiced && j == accent_phrase.moras.len() - 2;
+                        let last_mora = (j == (accent_phrase.moras.len() - 1) || next_unvoiced && j == accent_phrase.moras.len() - 2) && particles[i].is_none();
                         if last_mora {
                             mora.vowel_length *= 1.25;
+                            tracer.fire(i, j, "phrase_final_lengthening");
                         }
 
-                        if j == 0 {
-                            mora.pitch = avg_pitch * 0.96;
-                        } else if j < accent {
-                            mora.pitch = avg_pitch * 1.03;
-                        } else if j == accent {
-                            mora.pitch = avg_pitch * 1.04;
-                        } else {
-                            mora.pitch = avg_pitch * 0.95;
-                        }
+                        // statements end level in the south, so only the length changes
                         if last_accent_phrase && last_mora {
                             mora.vowel_length *= 1.25;
-                            if accent_phrase.is_interrogative {
-                                mora.pitch *= 1.04;
-                            } else {
-                                mora.pitch *= 1.0;
-                            }
                         }
                         accent_phrase.moras[j] = mora;
                     }
+
+                    if let Some((len, particle)) = particles[i] {
+                        dialect::apply_final_particle(accent_phrase, len, particle, pitch, i, tracer);
+                    } else if let Some(question) = questions[i] {
+                        dialect::apply_question_contour(accent_phrase, question, question_contours.get(question), pitch, i, tracer);
+                    }
+
+                    if let Some(prenasalization) = &profile.prenasalization {
+                        dialect::apply_prenasalization(accent_phrase, &query.accent_phrases[i], prenasalization, i, tracer);
+                    }
                 }
                 query.accent_phrases = accent_phrases;
-
-                params.apply(&mut query);
-                query
             },
         }
+
+        if let Some(humanize) = &profile.humanize {
+            dialect::humanize(&mut query.accent_phrases, humanize, pitch, tracer);
+        }
+        kana::update_query(&mut query);
+
+        params.apply(&mut query);
+        query
     }
 }
 
+/// Audio only
+type AudioResult = Result<Vec<u8>, InternalError>;
+
+/// Audio and the timing of each line
+type ScriptResult = Result<(Vec<u8>, Manifest), InternalError>;
+
+/// Audio and, when requested, the rule trace of each sentence
+type SynthesisResult = Result<(Vec<u8>, Vec<SentenceTrace>), InternalError>;
+
 #[derive(Debug)]
 enum EngineRequest {
-    Synthesis(EngineRequestData<(String, SynthesisOptions), Result<Vec<u8>, InternalError>>),
+    Synthesis(Box<EngineRequestData<(String, SynthesisOptions, bool), SynthesisResult>>),
     GetSpeakers(EngineRequestData<(), Result<Vec<Speaker>, InternalError>>),
+    SetUserDict(EngineRequestData<UserDict, ()>),
+    SynthesisSsml(Box<EngineRequestData<(SsmlDocument, SynthesisOptions), AudioResult>>),
+    SynthesisScript(Box<EngineRequestData<(Script, SynthesisOptions), ScriptResult>>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -349,10 +456,167 @@ fn get_speakers() -> Result<BTreeMap<u32, Speaker>, InternalError> {
     Ok(result)
 }
 
+fn measure_speaker_pitch(vvc: &VoicevoxCore, speaker_id: u32) -> Option<SpeakerPitch> {
+    let json = match vvc.audio_query(CALIBRATION_TEXT, speaker_id, vvcore::AudioQueryOptions { kana: false }) {
+        Ok(json) => json,
+        Err(e) => {
+            log::warn!("Failed to calibrate speaker {}: {}", speaker_id, VoicevoxCore::error_result_to_message(e));
+            return None;
+        },
+    };
+    let query: types::AudioQuery = serde_json::from_str(json.as_str()).ok()?;
+    let pitch = SpeakerPitch::from_query(&query)?;
+    log::debug!("Calibrated speaker {}: mean={:.3}", speaker_id, pitch.mean);
+    Some(pitch)
+}
+
+fn audio_query(vvc: &VoicevoxCore, text: &str, speaker_id: u32, kana: bool) -> Result<types::AudioQuery, InternalError> {
+    let json = vvc.audio_query(text, speaker_id, vvcore::AudioQueryOptions { kana })
+        .map_err(|e| InternalError::new(VoicevoxCore::error_result_to_message(e)))?;
+    serde_json::from_str(json.as_str()).map_err(|e| {
+        log::error!("Failed to parse JSON: {}", e);
+        InternalError::new("Failed to parse JSON")
+    })
+}
+
+/// Queries `text` with the words of the user dictionary read as registered.
+///
+/// The words are replaced by their readings so the whole text is still
+/// analysed together (particles after a word keep their reading), then the
+/// accent of each phrase starting with a word is set and the query is made
+/// again from the kana, so pitch and lengths follow the new accent.
+fn audio_query_with_user_dict(vvc: &VoicevoxCore, text: &str, user_dict: &UserDict, speaker_id: u32) -> Result<types::AudioQuery, InternalError> {
+    let segments = user_dict.segments(text);
+    let words = segments.iter().filter_map(|segment| match segment {
+        Segment::Word(word) => Some(*word),
+        Segment::Text(_) => None,
+    }).collect::<Vec<_>>();
+    if words.is_empty() {
+        return audio_query(vvc, text, speaker_id, false);
+    }
+
+    let substituted = segments.iter().map(|segment| match segment {
+        Segment::Word(word) => word.reading.as_str(),
+        Segment::Text(text) => text,
+    }).collect::<String>();
+    let mut query = audio_query(vvc, &substituted, speaker_id, false)?;
+
+    let mut words = words.into_iter().peekable();
+    for phrase in query.accent_phrases.iter_mut() {
+        let Some(word) = words.peek() else {
+            break;
+        };
+        let mut reading = word.reading.as_str();
+        let mut length = 0;
+        for mora in &phrase.moras {
+            match reading.strip_prefix(mora.text.as_str()) {
+                Some(rest) => {
+                    reading = rest;
+                    length += 1;
+                },
+                None => break,
+            }
+            if reading.is_empty() {
+                break;
+            }
+        }
+        if reading.is_empty() {
+            phrase.accent = if word.accent == 0 { phrase.moras.len() as i32 } else { word.accent.min(length) as i32 };
+            words.next();
+        }
+    }
+    if words.peek().is_some() {
+        log::debug!("Some user dictionary words do not start an accent phrase in: {}", text);
+    }
+
+    let kana = kana::create_kana(&query.accent_phrases);
+    audio_query(vvc, &kana, speaker_id, true).or_else(|e| {
+        log::warn!("Failed to read {} with the user dictionary: {}", kana, e);
+        Ok(query)
+    })
+}
+
+/// Silence between the pieces of an over-long sentence, in seconds
+const CLAUSE_PAUSE: f64 = 0.1;
+
+/// Text synthesized with one `audio_query`: a sentence or a part of one
+struct SynthesisUnit {
+    text: String,
+    /// `text` is AquesTalk kana rather than written Japanese
+    is_kana: bool,
+    quote: Option<QuoteStyle>,
+    pause_before: Option<f64>,
+    pause_after: Option<f64>,
+}
+
+impl SynthesisUnit {
+    fn sentence(text: String) -> Self {
+        Self {
+            text,
+            is_kana: false,
+            quote: None,
+            pause_before: None,
+            pause_after: None,
+        }
+    }
+
+    fn kana(kana: String) -> Self {
+        Self {
+            is_kana: true,
+            ..Self::sentence(kana)
+        }
+    }
+}
+
+/// Options of the request with the variant and strength set by an SSML element.
+///
+/// Switching to the other variant uses its default profile.
+fn styled_options(options: &SynthesisOptions, style: &SsmlStyle) -> SynthesisOptions {
+    let mut options = options.clone();
+    if let Some(variant) = style.variant.filter(|variant| *variant != options.variant) {
+        options.variant = variant;
+        options.profile = variant.default_profile();
+    }
+    if let Some(strength) = style.strength {
+        options.dialect_strength = strength;
+    }
+    options
+}
+
+/// Moves the pitch and lengths of `query` back toward the standard reading.
+///
+/// Only phrases whose moras the dialect rules left in place are blended;
+/// the rest keep the dialect output.
+fn blend_with_standard(query: &mut types::AudioQuery, standard: &types::AudioQuery, strength: f64) {
+    let blend = |standard: f64, dialect: f64| standard + (dialect - standard) * strength;
+    if query.accent_phrases.len() != standard.accent_phrases.len() {
+        return;
+    }
+    for (phrase, standard) in query.accent_phrases.iter_mut().zip(&standard.accent_phrases) {
+        if phrase.moras.len() != standard.moras.len() {
+            continue;
+        }
+        for (mora, standard) in phrase.moras.iter_mut().zip(&standard.moras) {
+            if mora.pitch > 0.0 && standard.pitch > 0.0 {
+                mora.pitch = blend(standard.pitch, mora.pitch);
+            }
+            mora.vowel_length = blend(standard.vowel_length, mora.vowel_length);
+            if let (Some(length), Some(standard)) = (mora.consonant_length.as_mut(), standard.consonant_length) {
+                *length = blend(standard, *length);
+            }
+        }
+        if let (Some(pause), Some(standard)) = (phrase.pause_mora.as_mut(), &standard.pause_mora) {
+            pause.vowel_length = blend(standard.vowel_length, pause.vowel_length);
+        }
+    }
+}
+
 struct Runner {
     vvc: VoicevoxCore,
     receiver: mpsc::Receiver<EngineRequest>,
     speakers: BTreeMap<u32, Speaker>,
+    speaker_pitches: BTreeMap<u32, SpeakerPitch>,
+    user_dict: UserDict,
 }
 
 impl Runner {
@@ -369,6 +633,11 @@ impl Runner {
         for speaker in speakers.values() {
             log::debug!("Found speaker: speaker_id={}, speaker_name={}, style={}", speaker.speaker_id, speaker.speaker_name, speaker.style_name);
         }
+
+        // every model is loaded above, so the baselines are measured once here
+        let speaker_pitches = speakers.keys()
+            .filter_map(|speaker_id| Some((*speaker_id, measure_speaker_pitch(&vvc, *speaker_id)?)))
+            .collect();
         
         let (req_sender, req_receiver) = mpsc::channel(100);
 
@@ -376,6 +645,8 @@ impl Runner {
             vvc,
             receiver: req_receiver,
             speakers,
+            speaker_pitches,
+            user_dict: user_dict::place_names(),
         };
 
         std::thread::spawn(move || {
@@ -387,101 +658,219 @@ impl Runner {
         })
     }
 
-    fn run(self) {
-        let vvc = self.vvc;
-        let mut receiver = self.receiver;
-
-        let text_splitter = TextSplitter::new();
-
-        'main_loop: loop {
-            match receiver.blocking_recv() {
+    fn run(mut self) {
+        loop {
+            match self.receiver.blocking_recv() {
                 Some(EngineRequest::GetSpeakers(data)) => {
                     let speakers = self.speakers.values().cloned().collect();
                     let _ = data.res_sender.send(Ok(speakers));
                 },
 
+                Some(EngineRequest::SetUserDict(data)) => {
+                    self.user_dict = data.req;
+                    let _ = data.res_sender.send(());
+                },
+
                 Some(EngineRequest::Synthesis(data)) => {
-                    let (text, options) = data.req;
+                    let (text, options, trace) = data.req;
 
-                    let sentences = text_splitter.split_text(&text);
+                    let mut samples = Vec::new();
+                    let mut traces = trace.then(Vec::new);
+                    let res = self.synthesize_text(&text, &options, &Prosody::default(), &mut samples, traces.as_mut())
+                        .map(|()| (audio::encode(&samples), traces.unwrap_or_default()));
+                    let _ = data.res_sender.send(res);
+                },
 
-                    let mut wav_sections = Vec::new();
+                Some(EngineRequest::SynthesisSsml(data)) => {
+                    let (document, options) = data.req;
 
-                    for text in sentences {
-                        if text.is_empty() {
-                            continue;
-                        }
+                    let res = self.synthesize_ssml(&document, &options).map(|samples| audio::encode(&samples));
+                    let _ = data.res_sender.send(res);
+                },
 
-                        let query: types::AudioQuery = match vvc.audio_query(&text, options.speaker_id, vvcore::AudioQueryOptions { kana: false }) {
-                            Ok(json) => {
-                                match serde_json::from_str(json.as_str()) {
-                                    Ok(v) => v,
-                                    Err(e) => {
-                                        log::error!("Failed to parse JSON: {}", e);
-                                        let err = InternalError::new("Failed to parse JSON");
-                                        let _ = data.res_sender.send(Err(err));
-                                        continue 'main_loop;
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                let msg = VoicevoxCore::error_result_to_message(e);
-                                let err = InternalError::new(msg);
-                                let _ = data.res_sender.send(Err(err));
-                                continue 'main_loop;
-                            }
-                        };
+                Some(EngineRequest::SynthesisScript(data)) => {
+                    let (script, options) = data.req;
 
-                        let mut query = options.variant.preprocess_audio_query(query, options.params);
+                    let res = self.synthesize_script(&script, &options).map(|(samples, manifest)| (audio::encode(&samples), manifest));
+                    let _ = data.res_sender.send(res);
+                },
 
-                        query.output_sampling_rate = 24000;
-                        query.output_stereo = false;
-                        query.post_phoneme_length = 0.2;
+                None => break,
+            }
+        }
 
-                        let json = serde_json::to_string(&query).unwrap();
+        log::warn!("Runner thread exited");
+    }
 
-                        log::debug!("Synthesizing with JSON: {}", json);
+    /// Baseline measured at start; the reference voice if calibration is
+    /// off or failed for this speaker.
+    fn speaker_pitch(&self, options: &SynthesisOptions) -> SpeakerPitch {
+        if !options.profile.speaker_calibration {
+            return SpeakerPitch::reference();
+        }
+        self.speaker_pitches.get(&options.speaker_id).copied().unwrap_or_else(SpeakerPitch::reference)
+    }
 
-                        let res = vvc.synthesis(&json, options.speaker_id, vvcore::SynthesisOptions { enable_interrogative_upspeak: false });
+    /// Reads plain text: normalization, lexical conversion, sentence
+    /// splitting, then one query per sentence or quoted part.
+    fn synthesize_text(&mut self, text: &str, options: &SynthesisOptions, prosody: &Prosody, samples: &mut Vec<i16>, mut traces: Option<&mut Vec<SentenceTrace>>) -> Result<(), InternalError> {
+        let pitch = self.speaker_pitch(options);
 
-                        match res {
-                            Ok(wav) => {
-                                wav_sections.push(wav.as_slice().to_owned());
-                                //let _ = data.res_sender.send(Ok(wav.as_slice().to_owned()));
-                            },
-                            Err(e) => {
-                                let msg = VoicevoxCore::error_result_to_message(e);
-                                let err = InternalError::new(msg);
-                                let _ = data.res_sender.send(Err(err));
-                                continue 'main_loop;
-                            },
-                        }
-                    }
+        let text = normalize::normalize(text, &options.normalization);
+        let text = match &options.profile.lexical_rules {
+            Some(rules) => lexical::convert(&text, rules),
+            None => text,
+        };
+        let text_splitter = TextSplitter::builder().max_length(options.max_sentence_length).build();
+        let sentences = text_splitter.split_sentences(&text);
+
+        // each sentence is read as a whole, or part by part with quotes set apart
+        let mut units: Vec<SynthesisUnit> = Vec::new();
+        for sentence in &sentences {
+            if sentence.continues {
+                if let Some(prev) = units.last_mut() {
+                    prev.pause_after = Some(CLAUSE_PAUSE);
+                }
+            }
+            let first = units.len();
+            match &options.profile.quotes {
+                Some(style) if sentence.parts.len() > 1 => {
+                    let last = sentence.parts.len() - 1;
+                    units.extend(sentence.parts.iter().enumerate().map(|(k, part)| SynthesisUnit {
+                        text: part.text.clone(),
+                        is_kana: false,
+                        quote: (part.kind == PartKind::Quote).then_some(*style),
+                        pause_before: (k > 0).then_some(style.pause),
+                        pause_after: (k < last).then_some(style.pause),
+                    }));
+                },
+                _ => units.push(SynthesisUnit::sentence(sentence.text())),
+            }
+            if sentence.continues {
+                units[first].pause_before = Some(CLAUSE_PAUSE);
+            }
+        }
 
-                    let mut wav = Cursor::new(Vec::new());
-                    let mut writer = WavWriter::new(&mut wav, hound::WavSpec {
-                        channels: 1,
-                        sample_rate: 24000,
-                        bits_per_sample: 16,
-                        sample_format: hound::SampleFormat::Int,
-                    }).unwrap();
-
-                    for section in wav_sections {
-                        let reader = WavReader::new(std::io::Cursor::new(section)).unwrap();
-                        for sample in reader.into_samples::<i16>() {
-                            writer.write_sample(sample.unwrap()).unwrap();
-                        }
-                    }
+        for unit in units {
+            if unit.text.is_empty() {
+                continue;
+            }
+
+            let user_dict = options.user_dict.as_ref().unwrap_or(&self.user_dict);
+            let query = audio_query_with_user_dict(&self.vvc, &unit.text, user_dict, options.speaker_id)?;
+            self.synthesize_query(query, &unit, options, &pitch, prosody, samples, traces.as_deref_mut())?;
+        }
+        Ok(())
+    }
+
+    /// Applies the dialect and the style of `unit` to a query and synthesizes it.
+    #[allow(clippy::too_many_arguments)]
+    fn synthesize_query(&self, query: types::AudioQuery, unit: &SynthesisUnit, options: &SynthesisOptions, pitch: &SpeakerPitch, prosody: &Prosody, samples: &mut Vec<i16>, traces: Option<&mut Vec<SentenceTrace>>) -> Result<(), InternalError> {
+        let mut tracer = if traces.is_some() { Tracer::new(&query) } else { Tracer::disabled() };
+        let strength = options.dialect_strength.clamp(0.0, 1.0);
+        let mut query = if strength > 0.0 {
+            let standard = (strength < 1.0).then(|| query.clone());
+            let mut query = options.variant.preprocess_audio_query(query, (!unit.is_kana).then_some(unit.text.as_str()), options.params, &options.profile, pitch, &mut tracer);
+            if let Some(standard) = &standard {
+                blend_with_standard(&mut query, standard, strength);
+            }
+            query
+        } else {
+            let mut query = query;
+            options.params.apply(&mut query);
+            query
+        };
+        if let Some(traces) = traces {
+            traces.push(SentenceTrace {
+                text: unit.text.clone(),
+                trace: tracer.finish(&query),
+            });
+        }
+
+        if query.accent_phrases.is_empty() {
+            log::debug!("Nothing to synthesize in: {}", unit.text);
+            return Ok(());
+        }
+
+        if let Some(style) = &unit.quote {
+            dialect::apply_quote_style(&mut query, style, pitch);
+        }
+        prosody.apply(&mut query);
+
+        query.output_sampling_rate = audio::SAMPLE_RATE as i32;
+        query.output_stereo = false;
+        query.post_phoneme_length = unit.pause_after.unwrap_or(0.2);
+        if unit.pause_before.is_some() {
+            // the previous part already ends with the pause
+            query.pre_phoneme_length = 0.0;
+        }
+
+        let json = serde_json::to_string(&query).unwrap();
 
-                    writer.finalize().unwrap();
+        log::debug!("Synthesizing with JSON: {}", json);
 
-                    let _ = data.res_sender.send(Ok(wav.into_inner()));
+        let wav = self.vvc.synthesis(&json, options.speaker_id, vvcore::SynthesisOptions { enable_interrogative_upspeak: false })
+            .map_err(|e| InternalError::new(VoicevoxCore::error_result_to_message(e)))?;
+        samples.extend(audio::decode(wav.as_slice())?);
+        Ok(())
+    }
+
+    fn synthesize_ssml(&mut self, document: &SsmlDocument, options: &SynthesisOptions) -> Result<Vec<i16>, InternalError> {
+        let mut samples = Vec::new();
+        for item in &document.items {
+            match item {
+                SsmlItem::Text { text, style } => {
+                    self.synthesize_text(text, &styled_options(options, style), &style.prosody, &mut samples, None)?;
+                },
+                SsmlItem::Kana { kana, style } => {
+                    let options = styled_options(options, style);
+                    let pitch = self.speaker_pitch(&options);
+                    let query = audio_query(&self.vvc, kana, options.speaker_id, true)?;
+                    self.synthesize_query(query, &SynthesisUnit::kana(kana.clone()), &options, &pitch, &style.prosody, &mut samples, None)?;
+                },
+                SsmlItem::Break(seconds) => {
+                    samples.extend(audio::silence(*seconds));
+                },
+                SsmlItem::Audio { src, fallback, style } => {
+                    let loaded = match &options.audio_dir {
+                        Some(dir) => audio::resolve(dir, src).and_then(|path| audio::load(&path, 10f64.powf(style.prosody.volume / 20.0))),
+                        None => Err(audio::AudioError::new("Audio files are disabled")),
+                    };
+                    match loaded {
+                        Ok(audio) => samples.extend(audio),
+                        Err(e) => {
+                            log::warn!("Cannot play {}: {}", src.display(), e);
+                            if !fallback.is_empty() {
+                                self.synthesize_text(fallback, &styled_options(options, style), &style.prosody, &mut samples, None)?;
+                            }
+                        },
+                    }
                 },
-                None => break,
             }
         }
+        Ok(samples)
+    }
 
-        log::warn!("Runner thread exited");
+    /// Reads each line of a script in its voice, separated by its gap.
+    fn synthesize_script(&mut self, script: &Script, options: &SynthesisOptions) -> Result<(Vec<i16>, Manifest), InternalError> {
+        let mut samples = Vec::new();
+        let mut manifest = Manifest::default();
+        for line in &script.lines {
+            let options = line.voice.options(options).map_err(|_| InternalError::new("Invalid parameter in script"))?;
+            samples.extend(audio::silence(line.gap));
+            let start = samples.len();
+            self.synthesize_text(&line.text, &options, &Prosody::default(), &mut samples, None)?;
+            manifest.lines.push(LineTiming {
+                line: line.line,
+                character: line.character.clone(),
+                text: line.text.clone(),
+                speaker_id: options.speaker_id,
+                start: audio::seconds(start),
+                end: audio::seconds(samples.len()),
+            });
+        }
+        manifest.duration = audio::seconds(samples.len());
+        Ok((samples, manifest))
     }
 }
 
@@ -507,15 +896,79 @@ impl EngineHandle {
         receiver.await.unwrap()
     }
 
-    pub fn synthesize_blocking(&self, text: String, options: SynthesisOptions) -> Result<Vec<u8>, InternalError> {
-        let (data, receiver) = EngineRequestData::new((text, options));
-        self.sender.blocking_send(EngineRequest::Synthesis(data)).unwrap();
+    /// Sets the user dictionary used by requests that do not bring their own.
+    ///
+    /// This replaces the default, the bundled place names; merge into
+    /// [`user_dict::place_names`] to keep them.
+    pub fn set_user_dict_blocking(&self, user_dict: UserDict) {
+        let (data, receiver) = EngineRequestData::new(user_dict);
+        self.sender.blocking_send(EngineRequest::SetUserDict(data)).unwrap();
         receiver.blocking_recv().unwrap()
     }
 
+    /// Sets the user dictionary used by requests that do not bring their own.
+    ///
+    /// This replaces the default, the bundled place names; merge into
+    /// [`user_dict::place_names`] to keep them.
+    pub async fn set_user_dict(&self, user_dict: UserDict) {
+        let (data, receiver) = EngineRequestData::new(user_dict);
+        self.sender.send(EngineRequest::SetUserDict(data)).await.unwrap();
+        receiver.await.unwrap()
+    }
+
+    /// Synthesizes a parsed SSML document; see [`crate::ssml`].
+    pub fn synthesize_ssml_blocking(&self, document: SsmlDocument, options: SynthesisOptions) -> Result<Vec<u8>, InternalError> {
+        let (data, receiver) = EngineRequestData::new((document, options));
+        self.sender.blocking_send(EngineRequest::SynthesisSsml(Box::new(data))).unwrap();
+        receiver.blocking_recv().unwrap()
+    }
+
+    /// Synthesizes a parsed SSML document; see [`crate::ssml`].
+    pub async fn synthesize_ssml(&self, document: SsmlDocument, options: SynthesisOptions) -> Result<Vec<u8>, InternalError> {
+        let (data, receiver) = EngineRequestData::new((document, options));
+        self.sender.send(EngineRequest::SynthesisSsml(Box::new(data))).await.unwrap();
+        receiver.await.unwrap()
+    }
+
+    /// Renders a dialogue script into one WAV, with the timing of each line;
+    /// see [`crate::script`]. Resolve the speakers of the script first.
+    pub fn render_script_blocking(&self, script: Script, options: SynthesisOptions) -> Result<(Vec<u8>, Manifest), InternalError> {
+        let (data, receiver) = EngineRequestData::new((script, options));
+        self.sender.blocking_send(EngineRequest::SynthesisScript(Box::new(data))).unwrap();
+        receiver.blocking_recv().unwrap()
+    }
+
+    /// Renders a dialogue script into one WAV, with the timing of each line;
+    /// see [`crate::script`]. Resolve the speakers of the script first.
+    pub async fn render_script(&self, script: Script, options: SynthesisOptions) -> Result<(Vec<u8>, Manifest), InternalError> {
+        let (data, receiver) = EngineRequestData::new((script, options));
+        self.sender.send(EngineRequest::SynthesisScript(Box::new(data))).await.unwrap();
+        receiver.await.unwrap()
+    }
+
+    pub fn synthesize_blocking(&self, text: String, options: SynthesisOptions) -> Result<Vec<u8>, InternalError> {
+        let (data, receiver) = EngineRequestData::new((text, options, false));
+        self.sender.blocking_send(EngineRequest::Synthesis(Box::new(data))).unwrap();
+        receiver.blocking_recv().unwrap().map(|(wav, _)| wav)
+    }
+
     pub async fn synthesize(&self, text: String, options: SynthesisOptions) -> Result<Vec<u8>, InternalError> {
-        let (data, receiver) = EngineRequestData::new((text, options));
-        self.sender.send(EngineRequest::Synthesis(data)).await.unwrap();
+        let (data, receiver) = EngineRequestData::new((text, options, false));
+        self.sender.send(EngineRequest::Synthesis(Box::new(data))).await.unwrap();
+        receiver.await.unwrap().map(|(wav, _)| wav)
+    }
+
+    /// Synthesizes and also returns the rule trace of every sentence.
+    pub fn synthesize_traced_blocking(&self, text: String, options: SynthesisOptions) -> Result<(Vec<u8>, Vec<SentenceTrace>), InternalError> {
+        let (data, receiver) = EngineRequestData::new((text, options, true));
+        self.sender.blocking_send(EngineRequest::Synthesis(Box::new(data))).unwrap();
+        receiver.blocking_recv().unwrap()
+    }
+
+    /// Synthesizes and also returns the rule trace of every sentence.
+    pub async fn synthesize_traced(&self, text: String, options: SynthesisOptions) -> Result<(Vec<u8>, Vec<SentenceTrace>), InternalError> {
+        let (data, receiver) = EngineRequestData::new((text, options, true));
+        self.sender.send(EngineRequest::Synthesis(Box::new(data))).await.unwrap();
         receiver.await.unwrap()
     }
 }
@@ -529,3 +982,170 @@ pub fn initialize<P: AsRef<Path>>(dir: P) -> Result<(), EngineError> {
     };
     ENGINE.set(handle).map_err(|_| EngineError::new(EngineErrorDescription::AlreadyInitialized))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::{CentralVowelMerger, HumanizeParams, SouthernContour};
+    use crate::trace::MoraState;
+    use crate::types::Vowel;
+    use proptest::prelude::*;
+
+    fn mora_strategy() -> impl Strategy<Value = types::Mora> {
+        let vowels = prop::sample::select(Vowel::ALL.iter().copied().filter(|v| *v != Vowel::Pau).collect::<Vec<_>>());
+        let consonants = prop::option::of(prop::sample::select(Consonant::ALL.to_vec()));
+        let pitch = prop_oneof![Just(0.0), 3.0..7.0f64];
+        (vowels, consonants, 0.0..0.5f64, pitch, 0.0..0.2f64).prop_map(|(vowel, consonant, vowel_length, pitch, consonant_length)| {
+            types::Mora {
+                text: "ア".to_string(),
+                vowel,
+                vowel_length,
+                pitch,
+                consonant,
+                consonant_length: consonant.map(|_| consonant_length),
+            }
+        })
+    }
+
+    fn pause_strategy() -> impl Strategy<Value = Option<types::Mora>> {
+        prop::option::of((0.0..1.0f64).prop_map(|vowel_length| types::Mora {
+            text: "、".to_string(),
+            vowel: Vowel::Pau,
+            vowel_length,
+            pitch: 0.0,
+            consonant: None,
+            consonant_length: None,
+        }))
+    }
+
+    fn query_strategy() -> impl Strategy<Value = types::AudioQuery> {
+        let phrase = (prop::collection::vec(mora_strategy(), 0..7), -2..9i32, pause_strategy(), any::<bool>())
+            .prop_map(|(moras, accent, pause_mora, is_interrogative)| types::AccentPhrase {
+                moras,
+                accent,
+                pause_mora,
+                is_interrogative,
+            });
+        (prop::collection::vec(phrase, 0..6), any::<bool>()).prop_map(|(accent_phrases, with_kana)| types::AudioQuery {
+            accent_phrases,
+            speed_scale: 1.0,
+            pitch_scale: 0.0,
+            intonation_scale: 1.0,
+            volume_scale: 1.0,
+            pre_phoneme_length: 0.1,
+            post_phoneme_length: 0.1,
+            output_sampling_rate: 24000,
+            output_stereo: false,
+            kana: with_kana.then(String::new),
+        })
+    }
+
+    fn profiles(variant: SynthesisVariant) -> Vec<DialectProfile> {
+        let mut everything = variant.default_profile();
+        everything.prenasalization = Some(Default::default());
+        everything.central_vowel_merger = Some(CentralVowelMerger::Strong);
+        everything.vowel_coalescence = Some(Default::default());
+        everything.southern_contour = SouthernContour::random(7);
+        everything.humanize = Some(HumanizeParams { amount: HumanizeParams::AMOUNT_MAX, ..Default::default() });
+        vec![variant.default_profile(), everything, DialectProfile::default()]
+    }
+
+    fn assert_finite(query: &types::AudioQuery) {
+        for phrase in &query.accent_phrases {
+            assert!(!phrase.moras.is_empty());
+            for mora in phrase.moras.iter().chain(phrase.pause_mora.iter()) {
+                assert!(mora.pitch.is_finite(), "pitch: {:?}", mora);
+                assert!(mora.vowel_length.is_finite(), "vowel_length: {:?}", mora);
+                assert!(mora.consonant_length.into_iter().all(f64::is_finite), "consonant_length: {:?}", mora);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn preprocess_never_panics(query in query_strategy(), mean in 4.5..6.5f64) {
+            let speakers = [SpeakerPitch::reference(), SpeakerPitch { mean }];
+            for variant in [SynthesisVariant::Northern, SynthesisVariant::Southern] {
+                for profile in profiles(variant) {
+                    for speaker in &speakers {
+                        let out = variant.apply_dialect(query.clone(), SynthesisParams::default(), &profile, speaker);
+                        assert_finite(&out);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn trace_covers_every_mora(query in query_strategy()) {
+            for variant in [SynthesisVariant::Northern, SynthesisVariant::Southern] {
+                for profile in profiles(variant) {
+                    let (out, trace) = variant.apply_dialect_traced(query.clone(), SynthesisParams::default(), &profile, &SpeakerPitch::reference());
+                    let transformed: Vec<_> = trace.moras.iter().filter_map(|m| m.transformed.clone()).collect();
+                    let moras: Vec<MoraState> = out.accent_phrases.iter().flat_map(|p| p.moras.iter().map(MoraState::from)).collect();
+                    prop_assert_eq!(transformed, moras);
+                    let originals = trace.moras.iter().filter(|m| m.original.is_some()).count();
+                    let kept = query.accent_phrases.iter().map(|p| p.moras.len()).sum::<usize>();
+                    prop_assert_eq!(originals, kept);
+                }
+            }
+        }
+
+        #[test]
+        fn blend_stays_between_standard_and_dialect(query in query_strategy(), strength in 0.0..=1.0f64) {
+            let variant = SynthesisVariant::Southern;
+            let dialect = variant.apply_dialect(query.clone(), SynthesisParams::default(), &variant.default_profile(), &SpeakerPitch::reference());
+            let mut blended = dialect.clone();
+            blend_with_standard(&mut blended, &query, strength);
+            prop_assume!(dialect.accent_phrases.len() == query.accent_phrases.len());
+
+            let phrases = blended.accent_phrases.iter().zip(&dialect.accent_phrases).zip(&query.accent_phrases);
+            for ((blended, dialect), standard) in phrases {
+                if dialect.moras.len() != standard.moras.len() {
+                    prop_assert_eq!(serde_json::to_value(blended).unwrap(), serde_json::to_value(dialect).unwrap());
+                    continue;
+                }
+                for ((b, d), s) in blended.moras.iter().zip(&dialect.moras).zip(&standard.moras) {
+                    let (low, high) = (d.vowel_length.min(s.vowel_length), d.vowel_length.max(s.vowel_length));
+                    prop_assert!(b.vowel_length >= low - 1e-9 && b.vowel_length <= high + 1e-9);
+                    if d.pitch > 0.0 && s.pitch > 0.0 {
+                        let (low, high) = (d.pitch.min(s.pitch), d.pitch.max(s.pitch));
+                        prop_assert!(b.pitch >= low - 1e-9 && b.pitch <= high + 1e-9);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn devoiced_sentence_does_not_panic() {
+        // 「す。」: a single devoiced mora with no pitch
+        let query = types::AudioQuery {
+            accent_phrases: vec![types::AccentPhrase {
+                moras: vec![types::Mora {
+                    text: "ス".to_string(),
+                    vowel: Vowel::UnvoicedU,
+                    vowel_length: 0.1,
+                    pitch: 0.0,
+                    consonant: Some(Consonant::S),
+                    consonant_length: Some(0.08),
+                }],
+                accent: 1,
+                pause_mora: None,
+                is_interrogative: false,
+            }],
+            speed_scale: 1.0,
+            pitch_scale: 0.0,
+            intonation_scale: 1.0,
+            volume_scale: 1.0,
+            pre_phoneme_length: 0.1,
+            post_phoneme_length: 0.1,
+            output_sampling_rate: 24000,
+            output_stereo: false,
+            kana: None,
+        };
+        for variant in [SynthesisVariant::Northern, SynthesisVariant::Southern] {
+            let out = variant.apply_dialect(query.clone(), SynthesisParams::default(), &variant.default_profile(), &SpeakerPitch::reference());
+            assert_finite(&out);
+        }
+    }
+}
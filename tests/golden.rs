@@ -0,0 +1,80 @@
+//! Golden tests for the dialect transforms.
+//!
+//! Every query in `tests/golden/queries` is run through both variants under
+//! each parameter set below, and compared with `tests/golden/expected`.
+//! A query with a `.txt` next to it is given the sentence it was made from,
+//! as in synthesis. Run with `UPDATE_GOLDEN=1` to rewrite the expectations
+//! after an intended prosody change, and review the diff.
+
+use tohoku_tts_voicevox::{types, SynthesisParams, SynthesisVariant};
+use tohoku_tts_voicevox::dialect::SpeakerPitch;
+
+use std::path::{Path, PathBuf};
+
+fn params() -> Vec<(&'static str, SynthesisParams)> {
+    vec![
+        ("default", SynthesisParams::default()),
+        ("low_slow", SynthesisParams::new(-2.0, 1.0, 0.9).unwrap()),
+        ("wide_fast", SynthesisParams::new(3.0, 1.5, 1.2).unwrap()),
+    ]
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn variant_name(variant: SynthesisVariant) -> &'static str {
+    match variant {
+        SynthesisVariant::Northern => "northern",
+        SynthesisVariant::Southern => "southern",
+    }
+}
+
+#[test]
+fn golden() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let expected_dir = golden_dir().join("expected");
+    if update {
+        std::fs::create_dir_all(&expected_dir).unwrap();
+    }
+
+    let mut queries: Vec<PathBuf> = std::fs::read_dir(golden_dir().join("queries")).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    queries.sort();
+    assert!(!queries.is_empty(), "no golden queries found");
+
+    let mut failures = Vec::new();
+    for path in &queries {
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let query: types::AudioQuery = serde_json::from_str(&std::fs::read_to_string(path).unwrap())
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let text = std::fs::read_to_string(path.with_extension("txt")).ok();
+
+        for variant in [SynthesisVariant::Northern, SynthesisVariant::Southern] {
+            for (params_name, params) in params() {
+                let profile = variant.default_profile();
+                let out = match &text {
+                    Some(text) => variant.apply_dialect_with_text(query.clone(), text.trim_end(), params, &profile, &SpeakerPitch::reference()),
+                    None => variant.apply_dialect(query.clone(), params, &profile, &SpeakerPitch::reference()),
+                };
+                let mut actual = serde_json::to_string_pretty(&out).unwrap();
+                actual.push('\n');
+
+                let expected_path = expected_dir.join(format!("{}.{}.{}.json", name, variant_name(variant), params_name));
+                if update {
+                    std::fs::write(&expected_path, &actual).unwrap();
+                    continue;
+                }
+                match std::fs::read_to_string(&expected_path) {
+                    Ok(expected) if expected == actual => {},
+                    Ok(_) => failures.push(format!("{}: output differs", expected_path.display())),
+                    Err(e) => failures.push(format!("{}: {}", expected_path.display(), e)),
+                }
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "golden mismatches (rerun with UPDATE_GOLDEN=1 to accept):\n{}", failures.join("\n"));
+}